@@ -0,0 +1,352 @@
+//! Achievements, listed in `achievements.ron` and earned through what happens in levels. Each save
+//! slot keeps the ones it has unlocked in a file next to its save.
+
+use std::{fs, path::PathBuf};
+
+use amethyst::{
+    core::{SystemBundle, Time},
+    ecs::*,
+    prelude::*,
+    shrev::{EventChannel, ReaderId},
+    Error,
+};
+
+use crate::{
+    asteroid::AsteroidType,
+    economy::Enterprise,
+    events::{AsteroidDelivered, AsteroidReacted, ExplosionTriggered, JumpStarted},
+    level::{Level, LevelTimer},
+    save::{save_dir, write_atomically, SaveError},
+};
+
+/// How long a toast stays on the HUD, in seconds.
+const TOAST_SECONDS: f32 = 4.0;
+/// Explosions older than this many are forgotten when looking for chains.
+const REMEMBERED_EXPLOSIONS: usize = 32;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum Goal {
+    /// This many explosions in a row, each no more than `seconds` after the one before.
+    ExplosionChain { count: usize, seconds: f32 },
+    /// A reaction that makes this.
+    Reaction { product: AsteroidType },
+    /// Delivering one of these, optionally by an enterprise that has never borrowed.
+    Delivery {
+        asteroid: AsteroidType,
+        #[serde(default)]
+        without_loan: bool,
+    },
+    /// Jumping out of this level, by name, before its par time.
+    UnderPar { level: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AchievementDesc {
+    pub id: String,
+    pub title: String,
+    pub description: String,
+    pub goal: Goal,
+}
+
+/// Every achievement there is to earn.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct AchievementBook {
+    pub achievements: Vec<AchievementDesc>,
+}
+
+/// The achievements unlocked by the enterprise in the chosen slot, by id.
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
+pub struct Unlocked {
+    ids: Vec<String>,
+}
+
+fn unlocked_path(slot: &str) -> PathBuf {
+    save_dir().join(format!("{}.achievements.ron", slot))
+}
+
+impl Unlocked {
+    /// Unreadable unlocks start over rather than getting in the way of playing.
+    pub fn load(slot: &str) -> Self {
+        let path = unlocked_path(slot);
+        if !path.exists() {
+            return Unlocked::default();
+        }
+        fs::read(&path)
+            .map_err(SaveError::from)
+            .and_then(|bytes| {
+                ron::de::from_bytes(&bytes).map_err(|error| SaveError::Corrupt(error.to_string()))
+            })
+            .unwrap_or_else(|error| {
+                log::warn!("Could not load achievements for {}: {}", slot, error);
+                Unlocked::default()
+            })
+    }
+
+    pub fn save(&self, slot: &str) {
+        if let Err(error) = write_atomically(&unlocked_path(slot), self) {
+            log::error!("Could not save achievements for {}: {}", slot, error);
+        }
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.ids.iter().any(|unlocked| unlocked == id)
+    }
+
+    /// Returns whether this is the first time it was unlocked.
+    pub fn unlock(&mut self, id: &str) -> bool {
+        if self.contains(id) {
+            false
+        } else {
+            self.ids.push(id.to_string());
+            true
+        }
+    }
+}
+
+/// Newly unlocked achievements waiting to be shown, the first with its time left on screen.
+#[derive(Default)]
+pub struct AchievementToasts {
+    pub queue: Vec<(String, f32)>,
+}
+
+/// How many of the latest explosions were each within `seconds` of the one before.
+fn chain_length(times: &[f32], seconds: f32) -> usize {
+    if times.is_empty() {
+        return 0;
+    }
+    1 + times
+        .windows(2)
+        .rev()
+        .take_while(|pair| pair[1] - pair[0] <= seconds)
+        .count()
+}
+
+#[derive(Default)]
+pub struct AchievementSystem {
+    delivered: Option<ReaderId<AsteroidDelivered>>,
+    reacted: Option<ReaderId<AsteroidReacted>>,
+    exploded: Option<ReaderId<ExplosionTriggered>>,
+    jumped: Option<ReaderId<JumpStarted>>,
+    clock: f32,
+    explosions: Vec<f32>,
+}
+
+impl<'s> System<'s> for AchievementSystem {
+    type SystemData = (
+        Read<'s, EventChannel<AsteroidDelivered>>,
+        Read<'s, EventChannel<AsteroidReacted>>,
+        Read<'s, EventChannel<ExplosionTriggered>>,
+        Read<'s, EventChannel<JumpStarted>>,
+        Read<'s, AchievementBook>,
+        Write<'s, Unlocked>,
+        Write<'s, AchievementToasts>,
+        Read<'s, Enterprise>,
+        Read<'s, Level>,
+        Read<'s, LevelTimer>,
+        Read<'s, Time>,
+    );
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.delivered = Some(
+            world
+                .write_resource::<EventChannel<AsteroidDelivered>>()
+                .register_reader(),
+        );
+        self.reacted = Some(
+            world
+                .write_resource::<EventChannel<AsteroidReacted>>()
+                .register_reader(),
+        );
+        self.exploded = Some(
+            world
+                .write_resource::<EventChannel<ExplosionTriggered>>()
+                .register_reader(),
+        );
+        self.jumped = Some(
+            world
+                .write_resource::<EventChannel<JumpStarted>>()
+                .register_reader(),
+        );
+    }
+
+    fn run(
+        &mut self,
+        (
+            delivered,
+            reacted,
+            exploded,
+            jumped,
+            book,
+            mut unlocked,
+            mut toasts,
+            enterprise,
+            level,
+            timer,
+            time,
+        ): Self::SystemData,
+    ) {
+        self.clock += time.delta_seconds();
+        let mut earned: Vec<&AchievementDesc> = Vec::new();
+        let mut check = |goal_met: &dyn Fn(&Goal) -> bool| {
+            for achievement in book.achievements.iter() {
+                if goal_met(&achievement.goal) {
+                    earned.push(achievement);
+                }
+            }
+        };
+
+        if let Some(reader) = &mut self.delivered {
+            for event in delivered.read(reader) {
+                check(&|goal| match goal {
+                    Goal::Delivery {
+                        asteroid,
+                        without_loan,
+                    } => {
+                        *asteroid == event.asteroid
+                            && !(*without_loan && enterprise.ever_borrowed())
+                    }
+                    _ => false,
+                });
+            }
+        }
+        if let Some(reader) = &mut self.reacted {
+            for event in reacted.read(reader) {
+                check(&|goal| match goal {
+                    Goal::Reaction { product } => {
+                        event.products.0.as_ref() == Some(product)
+                            || event.products.1.as_ref() == Some(product)
+                    }
+                    _ => false,
+                });
+            }
+        }
+        if let Some(reader) = &mut self.exploded {
+            for _ in exploded.read(reader) {
+                self.explosions.push(self.clock);
+                if self.explosions.len() > REMEMBERED_EXPLOSIONS {
+                    self.explosions.remove(0);
+                }
+                let explosions = &self.explosions;
+                check(&|goal| match goal {
+                    Goal::ExplosionChain { count, seconds } => {
+                        chain_length(explosions, *seconds) >= *count
+                    }
+                    _ => false,
+                });
+            }
+        }
+        if let Some(reader) = &mut self.jumped {
+            for event in jumped.read(reader) {
+                check(&|goal| match goal {
+                    Goal::UnderPar { level: name } => {
+                        *name == event.level
+                            && level
+                                .par_time
+                                .map_or(false, |par_time| timer.elapsed <= par_time)
+                    }
+                    _ => false,
+                });
+            }
+        }
+
+        for achievement in earned {
+            if unlocked.unlock(&achievement.id) {
+                toasts
+                    .queue
+                    .push((achievement.title.clone(), TOAST_SECONDS));
+            }
+        }
+    }
+}
+
+pub struct AchievementBundle;
+impl<'a, 'b> SystemBundle<'a, 'b> for AchievementBundle {
+    fn build(
+        self,
+        _world: &mut World,
+        dispatcher: &mut DispatcherBuilder<'a, 'b>,
+    ) -> Result<(), Error> {
+        dispatcher.add(AchievementSystem::default(), "achievements", &[]);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        economy::DEFAULT_FUEL_PRICE,
+        test_support::{load_asset, TestWorldBuilder},
+    };
+
+    #[test]
+    fn chains_count_back_from_the_latest() {
+        assert_eq!(chain_length(&[], 1.0), 0);
+        assert_eq!(chain_length(&[0.0, 5.0, 5.5, 6.0], 1.0), 3);
+        assert_eq!(chain_length(&[0.0, 5.0, 5.5, 7.0], 1.0), 1);
+    }
+
+    #[test]
+    fn events_unlock_achievements_once() {
+        let mut test = TestWorldBuilder::new()
+            .with(AchievementSystem::default(), "achievements")
+            .build();
+        test.world
+            .insert(load_asset::<AchievementBook>("achievements.ron"));
+        for _ in 0..2 {
+            test.world
+                .write_resource::<EventChannel<AsteroidReacted>>()
+                .single_write(AsteroidReacted {
+                    reactants: (AsteroidType::AcidMedium, AsteroidType::AcidMedium),
+                    products: (Some(AsteroidType::AcidBig), None),
+                });
+            test.run(1);
+        }
+        for _ in 0..3 {
+            test.world
+                .write_resource::<EventChannel<ExplosionTriggered>>()
+                .single_write(ExplosionTriggered {
+                    position: (0.0, 0.0),
+                    strength: 1.0,
+                });
+            test.run(1);
+        }
+        let unlocked = test.world.read_resource::<Unlocked>();
+        let toasts = test.world.read_resource::<AchievementToasts>();
+        assert!(unlocked.contains("refinery"));
+        assert!(unlocked.contains("chain_reaction"));
+        assert_eq!(toasts.queue.len(), 2);
+    }
+
+    /// Whether delivering an artifact earns "free_and_clear" for this enterprise.
+    fn artifact_is_free_and_clear(mut enterprise: Enterprise) -> bool {
+        let income = enterprise.deliver(&AsteroidType::Artifact, 100.0, 100.0);
+        let mut test = TestWorldBuilder::new()
+            .with(AchievementSystem::default(), "achievements")
+            .with_enterprise(enterprise)
+            .build();
+        test.world
+            .insert(load_asset::<AchievementBook>("achievements.ron"));
+        test.world
+            .write_resource::<EventChannel<AsteroidDelivered>>()
+            .single_write(AsteroidDelivered {
+                asteroid: AsteroidType::Artifact,
+                mass: 100.0,
+                income,
+            });
+        test.run(1);
+        let unlocked = test.world.read_resource::<Unlocked>();
+        unlocked.contains("free_and_clear")
+    }
+
+    #[test]
+    fn repaid_loans_still_count_against_borrowing_goals() {
+        assert!(artifact_is_free_and_clear(Enterprise::begin_enterprise()));
+        let mut enterprise = Enterprise::begin_enterprise();
+        enterprise.burn_fuel(10.0);
+        enterprise.refuel(DEFAULT_FUEL_PRICE);
+        assert!(enterprise.debt() > 0);
+        assert!(!artifact_is_free_and_clear(enterprise));
+    }
+}
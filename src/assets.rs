@@ -1,8 +1,3 @@
-use std::{
-    fs::File,
-    io::{BufRead, BufReader},
-};
-
 use amethyst::{
     assets::{AssetStorage, Directory, Format, Handle, Loader, ProgressCounter, RonFormat, Source},
     audio::{
@@ -18,12 +13,17 @@ use amethyst::{
 use serde::Deserialize;
 
 use crate::{
+    achievements::AchievementBook,
+    chemistry::{Chemistry, ChemistryHandle},
     delivery::DeliveryZone,
-    economy::Enterprise,
+    dev::DevMode,
     level::{Level, LevelHandle},
-    menu::{CardDesc, MenuState, MenuTransition},
+    materials::{Materials, MaterialsHandle},
+    menu::MenuState,
     physics::PhysicsHandle,
-    ASSETS,
+    replay::ReplayMode,
+    rng::StartupSeed,
+    GameplayState, ASSETS,
 };
 
 pub fn load_sound_file<'a, N>(
@@ -80,6 +80,26 @@ pub fn load_level<'a>(
     loader.load(path, RonFormat, progress, &level_storage)
 }
 
+pub fn load_chemistry<'a>(
+    world: &mut World,
+    path: &str,
+    progress: &'a mut ProgressCounter,
+) -> ChemistryHandle {
+    let loader = world.read_resource::<Loader>();
+    let chemistry_storage = world.read_resource::<AssetStorage<Chemistry>>();
+    loader.load(path, RonFormat, progress, &chemistry_storage)
+}
+
+pub fn load_materials<'a>(
+    world: &mut World,
+    path: &str,
+    progress: &'a mut ProgressCounter,
+) -> MaterialsHandle {
+    let loader = world.read_resource::<Loader>();
+    let materials_storage = world.read_resource::<AssetStorage<Materials>>();
+    loader.load(path, RonFormat, progress, &materials_storage)
+}
+
 pub type SpriteRes<'s> = Option<Read<'s, SpriteStorage>>;
 
 #[derive(Clone)]
@@ -99,6 +119,18 @@ impl<'s> SpriteHandles for Option<Read<'s, SpriteStorage>> {
 #[derive(Clone)]
 pub struct LevelStorage {
     pub levels: Vec<LevelHandle>,
+    /// Where each level was loaded from, relative to the assets directory.
+    pub paths: Vec<String>,
+}
+
+#[derive(Clone)]
+pub struct ChemistryStorage {
+    pub chemistry: ChemistryHandle,
+}
+
+#[derive(Clone)]
+pub struct MaterialStorage {
+    pub materials: MaterialsHandle,
 }
 
 #[derive(Clone)]
@@ -143,6 +175,10 @@ pub struct LoadingState {
     progress: Option<ProgressCounter>,
     assets: Option<ASSETS>,
     levels: Vec<String>,
+    achievements: AchievementBook,
+    seed: Option<u64>,
+    dev: DevMode,
+    replay: ReplayMode,
 }
 
 impl LoadingState {
@@ -156,13 +192,71 @@ impl LoadingState {
             progress: None,
             assets: None,
             levels,
+            achievements: AchievementBook::default(),
+            seed: None,
+            dev: DevMode::default(),
+            replay: ReplayMode::Live,
         })
     }
+
+    pub fn with_achievements(mut self, directory: Directory, path: &str) -> amethyst::Result<Self> {
+        let val = directory.load(path)?;
+        let mut de = ron::de::Deserializer::from_bytes(&val)?;
+        self.achievements = AchievementBook::deserialize(&mut de)?;
+        de.end()?;
+        Ok(self)
+    }
+
+    pub fn with_seed(mut self, seed: Option<u64>) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn with_dev_mode(mut self, dev: bool) -> Self {
+        self.dev = DevMode(dev);
+        self
+    }
+
+    pub fn with_replay(mut self, replay: ReplayMode) -> Self {
+        self.replay = replay;
+        self
+    }
+
+    /// A replay skips the menus and starts straight into its recorded level.
+    fn replay_state(&self, world: &World) -> Option<GameplayState> {
+        if let ReplayMode::Playback(replay) = &*world.read_resource::<ReplayMode>() {
+            let assets = self.assets.clone().unwrap();
+            let level_storage = world.read_resource::<AssetStorage<Level>>();
+            let level = assets.1.levels.iter().find(|handle| {
+                level_storage
+                    .get(handle)
+                    .map_or(false, |level| level.reference.name == replay.level)
+            });
+            match level {
+                Some(level) => Some(GameplayState {
+                    level: level.clone(),
+                    enterprise: replay.enterprise.clone(),
+                    assets,
+                }),
+                None => {
+                    log::error!("No level named {} to replay", replay.level);
+                    None
+                }
+            }
+        } else {
+            None
+        }
+    }
 }
 
 impl SimpleState for LoadingState {
     fn on_start(&mut self, mut data: StateData<'_, GameData<'_, '_>>) {
         data.world.register::<PhysicsHandle>();
+        data.world.insert(StartupSeed(self.seed));
+        data.world.insert(self.dev);
+        data.world.insert(std::mem::take(&mut self.achievements));
+        data.world
+            .insert(std::mem::replace(&mut self.replay, ReplayMode::Live));
         // data.world.insert(AssetStorage::<TiledMap>::default());
 
         init_output(data.world);
@@ -174,6 +268,8 @@ impl SimpleState for LoadingState {
             .iter()
             .map(|path| load_level(data.world, path.to_string(), &mut progress_counter))
             .collect();
+        let chemistry = load_chemistry(data.world, "reactions.ron", &mut progress_counter);
+        let materials = load_materials(data.world, "materials.ron", &mut progress_counter);
         let main_theme = load_sound_file(data.world, "audio/SpaceTheme.mp3", &mut progress_counter);
         let jump_theme =
             load_sound_file(data.world, "audio/JumpingTheme.mp3", &mut progress_counter);
@@ -181,48 +277,27 @@ impl SimpleState for LoadingState {
         self.progress = Some(progress_counter);
         self.assets = Some((
             SpriteStorage { sprites },
-            LevelStorage { levels },
+            LevelStorage {
+                levels,
+                paths: self.levels.clone(),
+            },
             SoundStorage {
                 main_theme,
                 jump_theme,
             },
+            ChemistryStorage { chemistry },
+            MaterialStorage { materials },
         ));
     }
 
     fn update(&mut self, data: &mut StateData<GameData>) -> SimpleTrans {
         if let Some(progress) = &self.progress {
             if progress.is_complete() {
-                let enterprise: Option<Enterprise> = {
-                    if let Ok(mut file) = File::open("enterprise.ron") {
-                        let mut reader = BufReader::new(file);
-                        let mut save = String::new();
-                        reader.read_line(&mut save);
-                        if let Ok(save) = ron::de::from_bytes(save.as_bytes()) {
-                            Some(save)
-                        } else {
-                            None
-                        }
-                    } else {
-                        None
-                    }
-                };
-                return SimpleTrans::Switch(Box::new(MenuState::card_menu(
+                if let Some(state) = self.replay_state(data.world) {
+                    return SimpleTrans::Switch(Box::new(state));
+                }
+                return SimpleTrans::Switch(Box::new(MenuState::slot_menu(
                     self.assets.clone().unwrap(),
-                    vec![
-                        (
-                            CardDesc::new("Begin Your Enterprise!", 0),
-                            MenuTransition::Begin,
-                        ),
-                        (
-                            CardDesc::new("Continue Your Enterprise!", 0),
-                            MenuTransition::Continue,
-                        ),
-                        (
-                            CardDesc::new("Retire For The Day...", 0),
-                            MenuTransition::Quit,
-                        ),
-                    ],
-                    enterprise,
                 )));
             }
         }
@@ -1,26 +1,37 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, ops::Deref};
 
 use amethyst::{
-    core::{SystemBundle, Transform},
+    core::{SystemBundle, Time, Transform},
     ecs::*,
     prelude::*,
     renderer::{sprite::SpriteSheetHandle, SpriteRender},
     shrev::EventChannel,
     Error,
 };
+use nalgebra::Vector2;
 use ncollide2d::{
     narrow_phase::ContactEvent,
     shape::{Ball, ShapeHandle},
 };
-use nphysics2d::object::{BodyStatus, ColliderDesc, DefaultColliderHandle, RigidBodyDesc};
+use nphysics2d::{
+    algebra::Velocity2,
+    object::{BodyStatus, ColliderDesc, DefaultColliderHandle, RigidBodyDesc},
+};
+use rand::Rng;
 
 use crate::{
     assets::{SpriteHandles, SpriteRes, SpriteStorage},
+    chemistry::Chemistry,
+    events::AsteroidReacted,
     explosions::{generate_explosion, ExplosionForceSystem},
+    materials::Materials,
+    particles::random_direction,
     physics::{Physics, PhysicsContactEvent, PhysicsDesc, PhysicsHandle},
+    placement::{Area, Placement},
+    rng::GameRng,
 };
 
-#[derive(Debug, PartialEq, Copy, Serialize, Deserialize, Clone, Hash, Eq)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Hash, Eq)]
 pub enum AsteroidType {
     // Mineral
     Big,
@@ -46,6 +57,8 @@ pub enum AsteroidType {
     // Gold
     EncasedArtifact,
     Artifact,
+    // Designer-defined, see materials.ron
+    Custom(String),
 }
 
 #[derive(Component, Debug)]
@@ -54,154 +67,43 @@ pub struct Asteroid {
     pub my_type: AsteroidType,
 }
 
-impl AsteroidType {
-    pub fn get_sprite_num(&self) -> usize {
-        match self {
-            // Mineral
-            AsteroidType::Big => 1,
-            AsteroidType::Medium => 2,
-            AsteroidType::Small => 3,
-            AsteroidType::Bitty => 4,
-            // Explosive
-            AsteroidType::Bomb => 10,
-            // Reactive
-            AsteroidType::Hydrogen => 27,
-            AsteroidType::Oxygen => 28,
-            AsteroidType::Sulphur => 68,
-            AsteroidType::Water => 29,
-            AsteroidType::WaterMedium => 37,
-            AsteroidType::WaterBig => 38,
-            AsteroidType::Acid => 53,
-            AsteroidType::AcidMedium => 54,
-            AsteroidType::AcidBig => 55,
-            // Ship
-            AsteroidType::ShipPiece(idx) => 56 + idx,
-            AsteroidType::ShipPieceTarnished(idx) => 62 + idx,
-            // Artifact
-            AsteroidType::EncasedArtifact => 69,
-            AsteroidType::Artifact => 70,
-        }
-    }
-    pub fn get_radius(&self) -> f32 {
-        match self {
-            // Mineral
-            AsteroidType::Big => 8.0,
-            AsteroidType::Medium => 6.0,
-            AsteroidType::Small => 4.0,
-            AsteroidType::Bitty => 2.0,
-            // Explosive
-            AsteroidType::Bomb => 4.0,
-            // Reactive
-            AsteroidType::Hydrogen => 4.0,
-            AsteroidType::Oxygen => 4.0,
-            AsteroidType::Sulphur => 4.0,
-            AsteroidType::Water | AsteroidType::Acid => 4.0,
-            AsteroidType::WaterMedium | AsteroidType::AcidMedium => 6.0,
-            AsteroidType::WaterBig | AsteroidType::AcidBig => 8.0,
-            // Ship
-            AsteroidType::ShipPiece(_) | AsteroidType::ShipPieceTarnished(_) => 4.0,
-            // Artifact
-            AsteroidType::EncasedArtifact => 8.0,
-            AsteroidType::Artifact => 6.0,
-        }
-    }
-    pub fn get_mass(&self) -> f32 {
-        match self {
-            AsteroidType::Big => 20.0,
-            AsteroidType::Medium => 10.0,
-            AsteroidType::Small => 5.0,
-            AsteroidType::Bitty => 4.0,
-            // Bomb
-            AsteroidType::Bomb => 20.0,
-            // Reactive
-            AsteroidType::Hydrogen => 4.0,
-            AsteroidType::Oxygen => 4.0,
-            AsteroidType::Water => 8.0,
-            AsteroidType::WaterMedium => 16.0,
-            AsteroidType::WaterBig => 32.0,
-            // Acids
-            AsteroidType::Sulphur => 4.0,
-            AsteroidType::Acid => 12.0,
-            AsteroidType::AcidMedium => 20.0,
-            AsteroidType::AcidBig => 36.0,
-            // Ship Pieces
-            AsteroidType::ShipPiece(_) | AsteroidType::ShipPieceTarnished(_) => 80.0,
-            // Artifact
-            AsteroidType::EncasedArtifact => 80.0,
-            AsteroidType::Artifact => 70.0,
-        }
-    }
-    pub fn get_base_ppm(&self) -> f32 {
-        match self {
-            AsteroidType::Bomb => 0.5,
-            AsteroidType::Big => 2.0,
-            AsteroidType::Medium => 1.5,
-            AsteroidType::Hydrogen => 1.5,
-            AsteroidType::Oxygen => 1.5,
-            AsteroidType::ShipPieceTarnished(_) => 0.1,
-            AsteroidType::EncasedArtifact => 0.5,
-            AsteroidType::Artifact => 600.0,
-            _ => 1.0,
-        }
-    }
-    pub fn explodes(&self, other: Self) -> Option<(f32, Vec<usize>)> {
-        match (self, other) {
-            (AsteroidType::Bomb, AsteroidType::Bomb) => Some((500_000.0, vec![36])),
-            (AsteroidType::Hydrogen, AsteroidType::Bomb) => Some((500_000.0, vec![34])),
-            (AsteroidType::Bomb, AsteroidType::Hydrogen) => Some((500_000.0, vec![34])),
-            _ => None,
-        }
-    }
-    pub fn reacts(&self, other: Self) -> Option<(Option<Self>, Option<Self>)> {
-        match (self, other) {
-            (AsteroidType::Hydrogen, AsteroidType::Oxygen)
-            | (AsteroidType::Oxygen, AsteroidType::Hydrogen) => {
-                Some((Some(AsteroidType::Water), None))
-            }
-            (AsteroidType::Water, AsteroidType::Water) => {
-                Some((Some(AsteroidType::WaterMedium), None))
-            }
-            (AsteroidType::WaterMedium, AsteroidType::WaterMedium) => {
-                Some((Some(AsteroidType::WaterBig), None))
-            }
-            (AsteroidType::Water, AsteroidType::Sulphur) => Some((Some(AsteroidType::Acid), None)),
-            (AsteroidType::WaterMedium, AsteroidType::Sulphur) => {
-                Some((Some(AsteroidType::AcidMedium), None))
-            }
-            (AsteroidType::WaterBig, AsteroidType::Sulphur) => {
-                Some((Some(AsteroidType::AcidBig), None))
-            }
-            (AsteroidType::Sulphur, AsteroidType::Water) => Some((None, Some(AsteroidType::Acid))),
-            (AsteroidType::Sulphur, AsteroidType::WaterMedium) => {
-                Some((None, Some(AsteroidType::AcidMedium)))
-            }
-            (AsteroidType::Sulphur, AsteroidType::WaterBig) => {
-                Some((None, Some(AsteroidType::AcidBig)))
-            }
-            (AsteroidType::Acid, AsteroidType::ShipPieceTarnished(idx)) => {
-                Some((None, Some(AsteroidType::ShipPiece(idx))))
-            }
-            (AsteroidType::ShipPieceTarnished(idx), AsteroidType::Acid) => {
-                Some((Some(AsteroidType::ShipPiece(*idx)), None))
-            }
-            _ => None,
-        }
-    }
+pub fn generate_asteroid(
+    builder: impl Builder,
+    sprites: SpriteSheetHandle,
+    materials: &Materials,
+    size: AsteroidType,
+    transform: Transform,
+) {
+    generate_moving_asteroid(
+        builder,
+        sprites,
+        materials,
+        size,
+        transform,
+        Vector2::zeros(),
+        0.0,
+    );
 }
 
-pub fn generate_asteroid(
+/// An asteroid that's already on its way somewhere, turning `spin` radians a second.
+pub fn generate_moving_asteroid(
     builder: impl Builder,
     sprites: SpriteSheetHandle,
+    materials: &Materials,
     size: AsteroidType,
     transform: Transform,
+    velocity: Vector2<f32>,
+    spin: f32,
 ) {
+    let material = materials.get(&size);
     let body = RigidBodyDesc::new()
-        .mass(size.get_mass())
+        .mass(material.mass)
+        .velocity(Velocity2::new(velocity, spin))
         .status(BodyStatus::Dynamic);
-    let shape = ShapeHandle::new(Ball::new(size.get_radius()));
+    let shape = ShapeHandle::new(Ball::new(material.radius));
     let collider = ColliderDesc::new(shape);
     let asteroid = builder
-        .with(SpriteRender::new(sprites, size.get_sprite_num()))
+        .with(SpriteRender::new(sprites, material.sprite_number))
         .with(PhysicsDesc::new(body, collider))
         .with(transform)
         .with(Asteroid { my_type: size })
@@ -211,127 +113,202 @@ pub fn generate_asteroid(
 pub fn resize_asteroid(entity: Entity) -> impl FnOnce(&mut World) + 'static + Sync + Send {
     move |world| {
         world.exec(
-            |(mut sprites, asteroids, handles, mut physics): (
+            |(mut sprites, asteroids, handles, mut physics, materials): (
                 WriteStorage<SpriteRender>,
                 ReadStorage<Asteroid>,
                 ReadStorage<PhysicsHandle>,
                 Write<Physics>,
+                Read<Materials>,
             )| {
                 if let (Some(sprite), Some(asteroid), Some(handle)) = (
                     sprites.get_mut(entity),
                     asteroids.get(entity),
                     handles.get(entity),
                 ) {
-                    physics.change_shape(
-                        handle,
-                        ShapeHandle::new(Ball::new(asteroid.my_type.get_radius())),
-                    );
-                    sprite.sprite_number = asteroid.my_type.get_sprite_num();
+                    let material = materials.get(&asteroid.my_type);
+                    physics.change_shape(handle, ShapeHandle::new(Ball::new(material.radius)));
+                    sprite.sprite_number = material.sprite_number;
                 }
             },
         );
     }
 }
 
+/// How many asteroids a mix puts in a field: an exact count, or so many per 100 by 100 area.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub enum Amount {
+    Count(usize),
+    Density(f32),
+}
+
+impl Amount {
+    pub fn count(&self, area: f32) -> usize {
+        match self {
+            Amount::Count(count) => *count,
+            Amount::Density(density) => (density * area / 10_000.0).round() as usize,
+        }
+    }
+}
+
+/// A weighted table of asteroid types and how many to draw from it.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct FieldMix {
+    pub types: Vec<(AsteroidType, f32)>,
+    pub amount: Amount,
+}
+
+impl FieldMix {
+    pub fn new(types: Vec<(AsteroidType, f32)>, count: usize) -> Self {
+        FieldMix {
+            types,
+            amount: Amount::Count(count),
+        }
+    }
+
+    /// The mixes behind a field's `normal`, `bombs`, `gases`, `sulphur`, `artifacts` and `debris`
+    /// counts. Minerals come out mostly medium and small with a few big and bitty ones, gases are
+    /// half hydrogen and half oxygen, and `debris.1` of the debris is tarnished.
+    pub fn preset(
+        normal: usize,
+        bombs: usize,
+        gases: usize,
+        sulphur: usize,
+        artifacts: usize,
+        (debris, tarnished): (usize, f32),
+    ) -> Vec<FieldMix> {
+        let tarnished = tarnished.max(0.0).min(1.0);
+        let debris_types = (0..6)
+            .map(|piece| (AsteroidType::ShipPiece(piece), (1.0 - tarnished) / 6.0))
+            .chain((0..6).map(|piece| (AsteroidType::ShipPieceTarnished(piece), tarnished / 6.0)))
+            .collect();
+        vec![
+            FieldMix::new(
+                vec![
+                    (AsteroidType::Big, 0.1),
+                    (AsteroidType::Bitty, 0.09),
+                    (AsteroidType::Medium, 0.405),
+                    (AsteroidType::Small, 0.405),
+                ],
+                normal,
+            ),
+            FieldMix::new(vec![(AsteroidType::Bomb, 1.0)], bombs),
+            FieldMix::new(
+                vec![(AsteroidType::Hydrogen, 1.0), (AsteroidType::Oxygen, 1.0)],
+                gases,
+            ),
+            FieldMix::new(vec![(AsteroidType::Sulphur, 1.0)], sulphur),
+            FieldMix::new(vec![(AsteroidType::EncasedArtifact, 1.0)], artifacts),
+            FieldMix::new(debris_types, debris),
+        ]
+        .into_iter()
+        .filter(|mix| mix.amount != Amount::Count(0))
+        .collect()
+    }
+
+    /// The room one of this mix's asteroids takes up on average, keeping `spacing` clear around
+    /// it.
+    pub fn footprint(&self, materials: &Materials, spacing: f32) -> f32 {
+        let total: f32 = self.types.iter().map(|(_, weight)| weight.max(0.0)).sum();
+        if total <= 0.0 {
+            return 0.0;
+        }
+        self.types
+            .iter()
+            .map(|(asteroid, weight)| {
+                let radius = materials.get(asteroid).radius + spacing / 2.0;
+                weight.max(0.0) / total * std::f32::consts::PI * radius * radius
+            })
+            .sum()
+    }
+
+    /// Draws a type in proportion to the weights. Nothing is drawn when no weight is positive.
+    pub fn pick(&self, rng: &mut impl Rng) -> Option<AsteroidType> {
+        let total: f32 = self.types.iter().map(|(_, weight)| weight.max(0.0)).sum();
+        if total <= 0.0 {
+            return None;
+        }
+        let mut remaining = rng.gen::<f32>() * total;
+        self.types
+            .iter()
+            .filter(|(_, weight)| *weight > 0.0)
+            .find(|(_, weight)| {
+                remaining -= weight;
+                remaining < 0.0
+            })
+            .or_else(|| self.types.iter().rev().find(|(_, weight)| *weight > 0.0))
+            .map(|(asteroid, _)| asteroid.clone())
+    }
+}
+
+/// Speed and spin ranges for a field's asteroids, which head off in random directions.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct FieldMotion {
+    pub speed: (f32, f32),
+    pub spin: (f32, f32),
+}
+
+fn between(rng: &mut impl Rng, (low, high): (f32, f32)) -> f32 {
+    low + rng.gen::<f32>() * (high - low)
+}
+
+/// Scatters each mix over `area`, leaving out asteroids `placement` can't find room for.
 pub fn generate_asteroid_field(
     world: &mut World,
-    size: (f32, f32),
-    asteroid_count: usize,
-    bomb_count: usize,
-    gas_count: usize,
-    sulphur_count: usize,
-    artifact_count: usize,
-    debris_count: (usize, f32),
-    transform: Transform,
+    rng: &mut GameRng,
+    area: &Area,
+    mixes: &[FieldMix],
+    motion: FieldMotion,
+    placement: &mut Placement,
 ) {
     let spritesheet = {
         let sprites = world.read_resource::<SpriteStorage>();
         sprites.sprites.clone()
     };
-    for _ in 0..asteroid_count {
-        let x = rand::random::<f32>() * size.0 - size.0 / 2.0;
-        let y = rand::random::<f32>() * size.1 - size.1 / 2.0;
-        let size = {
-            if rand::random::<f32>() > 0.9 {
-                AsteroidType::Big
-            } else if rand::random::<f32>() > 0.9 {
-                AsteroidType::Bitty
-            } else if rand::random() {
-                AsteroidType::Medium
+    let materials = { world.read_resource::<Materials>().deref().clone() };
+    let mut skipped = 0;
+    for mix in mixes {
+        for _ in 0..mix.amount.count(area.area()) {
+            let asteroid = match mix.pick(rng) {
+                Some(asteroid) => asteroid,
+                None => break,
+            };
+            let (x, y) = match placement
+                .place(rng, materials.get(&asteroid).radius, |rng| area.sample(rng))
+            {
+                Some(point) => point,
+                None => {
+                    skipped += 1;
+                    continue;
+                }
+            };
+            let velocity = if motion.speed == (0.0, 0.0) {
+                Vector2::zeros()
             } else {
-                AsteroidType::Small
-            }
-        };
-        let mut transform = transform.clone();
-        transform.append_translation_xyz(x, y, 0.0);
-        generate_asteroid(world.create_entity(), spritesheet.clone(), size, transform);
-    }
-    for _ in 0..bomb_count {
-        let x = rand::random::<f32>() * size.0;
-        let y = rand::random::<f32>() * size.1;
-        let mut transform = transform.clone();
-        transform.append_translation_xyz(x, y, 0.0);
-        generate_asteroid(
-            world.create_entity(),
-            spritesheet.clone(),
-            AsteroidType::Bomb,
-            transform,
-        );
-    }
-    for _ in 0..gas_count {
-        let x = rand::random::<f32>() * size.0;
-        let y = rand::random::<f32>() * size.1;
-        let mut transform = transform.clone();
-        transform.append_translation_xyz(x, y, 0.0);
-        generate_asteroid(
-            world.create_entity(),
-            spritesheet.clone(),
-            if rand::random() {
-                AsteroidType::Hydrogen
+                random_direction(rng) * between(rng, motion.speed)
+            };
+            let spin = if motion.spin == (0.0, 0.0) {
+                0.0
             } else {
-                AsteroidType::Oxygen
-            },
-            transform,
-        );
-    }
-    for _ in 0..sulphur_count {
-        let x = rand::random::<f32>() * size.0;
-        let y = rand::random::<f32>() * size.1;
-        let mut transform = transform.clone();
-        transform.append_translation_xyz(x, y, 0.0);
-        generate_asteroid(
-            world.create_entity(),
-            spritesheet.clone(),
-            AsteroidType::Sulphur,
-            transform,
-        );
+                between(rng, motion.spin)
+            };
+            let mut transform = Transform::default();
+            transform.set_translation_xyz(x, y, 0.0);
+            generate_moving_asteroid(
+                world.create_entity(),
+                spritesheet.clone(),
+                &materials,
+                asteroid,
+                transform,
+                velocity,
+                spin,
+            );
+        }
     }
-    for _ in 0..artifact_count {
-        let x = rand::random::<f32>() * size.0;
-        let y = rand::random::<f32>() * size.1;
-        let mut transform = transform.clone();
-        transform.append_translation_xyz(x, y, 0.0);
-        generate_asteroid(
-            world.create_entity(),
-            spritesheet.clone(),
-            AsteroidType::EncasedArtifact,
-            transform,
-        );
-    }
-    for _ in 0..debris_count.0 {
-        let x = rand::random::<f32>() * size.0;
-        let y = rand::random::<f32>() * size.1;
-        let mut transform = transform.clone();
-        transform.append_translation_xyz(x, y, 0.0);
-        generate_asteroid(
-            world.create_entity(),
-            spritesheet.clone(),
-            if rand::random::<f32>() > debris_count.1 {
-                AsteroidType::ShipPiece((rand::random::<f32>() * 6.0) as usize)
-            } else {
-                AsteroidType::ShipPieceTarnished((rand::random::<f32>() * 6.0) as usize)
-            },
-            transform,
+    if skipped > 0 {
+        log::warn!(
+            "{} asteroids found no room in the field around {:?}",
+            skipped,
+            area.center()
         );
     }
 }
@@ -350,9 +327,11 @@ impl<'s> System<'s> for AsteroidExplosionSystem {
         Write<'s, Physics>,
         Read<'s, LazyUpdate>,
         SpriteRes<'s>,
+        Read<'s, Chemistry>,
     );
 
     fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
         self.reader = Some(
             world
                 .write_resource::<EventChannel<PhysicsContactEvent>>()
@@ -362,7 +341,7 @@ impl<'s> System<'s> for AsteroidExplosionSystem {
 
     fn run(
         &mut self,
-        (events, handles, asteroids, entities, physics, update, sprites): Self::SystemData,
+        (events, handles, asteroids, entities, physics, update, sprites, chemistry): Self::SystemData,
     ) {
         if let Some(reader) = &mut self.reader {
             for event in events.read(reader) {
@@ -376,7 +355,7 @@ impl<'s> System<'s> for AsteroidExplosionSystem {
                                 (asteroids.get(*a), asteroids.get(*b))
                             {
                                 if let Some((strength, particles)) =
-                                    asteroid_a.my_type.explodes(asteroid_b.my_type)
+                                    chemistry.explodes(&asteroid_a.my_type, &asteroid_b.my_type)
                                 {
                                     if let Some((Some(location_a), Some(location_b))) = handles
                                         .get(*a)
@@ -430,9 +409,12 @@ impl<'s> System<'s> for AsteroidReactionSystem {
         Write<'s, Physics>,
         Read<'s, LazyUpdate>,
         SpriteRes<'s>,
+        Read<'s, Chemistry>,
+        Write<'s, EventChannel<AsteroidReacted>>,
     );
 
     fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
         self.reader = Some(
             world
                 .write_resource::<EventChannel<PhysicsContactEvent>>()
@@ -442,7 +424,17 @@ impl<'s> System<'s> for AsteroidReactionSystem {
 
     fn run(
         &mut self,
-        (events, handles, mut asteroids, entities, physics, update, sprites): Self::SystemData,
+        (
+            events,
+            handles,
+            mut asteroids,
+            entities,
+            physics,
+            update,
+            sprites,
+            chemistry,
+            mut reacted,
+        ): Self::SystemData,
     ) {
         if let Some(reader) = &mut self.reader {
             for event in events.read(reader) {
@@ -456,7 +448,18 @@ impl<'s> System<'s> for AsteroidReactionSystem {
                                 if let (Some(asteroid_a), Some(asteroid_b)) =
                                     (asteroids.get(*a), asteroids.get(*b))
                                 {
-                                    asteroid_a.my_type.reacts(asteroid_b.my_type)
+                                    chemistry
+                                        .reacts(&asteroid_a.my_type, &asteroid_b.my_type)
+                                        .map(|products| {
+                                            reacted.single_write(AsteroidReacted {
+                                                reactants: (
+                                                    asteroid_a.my_type.clone(),
+                                                    asteroid_b.my_type.clone(),
+                                                ),
+                                                products: products.clone(),
+                                            });
+                                            products
+                                        })
                                 } else {
                                     None
                                 }
@@ -489,6 +492,89 @@ impl<'s> System<'s> for AsteroidReactionSystem {
     }
 }
 
+/// Launches asteroids from where it stands, `rate` a second, until it has launched `remaining`.
+#[derive(Component, Debug)]
+#[storage(DenseVecStorage)]
+pub struct AsteroidStream {
+    pub velocity: Vector2<f32>,
+    pub rate: f32,
+    pub types: Vec<AsteroidType>,
+    pub remaining: Option<usize>,
+    /// Seconds until the next launch.
+    pub countdown: f32,
+}
+
+impl AsteroidStream {
+    pub fn new(
+        velocity: Vector2<f32>,
+        rate: f32,
+        types: Vec<AsteroidType>,
+        remaining: Option<usize>,
+    ) -> Self {
+        AsteroidStream {
+            velocity,
+            rate,
+            types,
+            remaining,
+            countdown: 0.0,
+        }
+    }
+
+    /// Counts down by `seconds`, returning how long ago each asteroid due to launch was due.
+    pub fn advance(&mut self, seconds: f32) -> Vec<f32> {
+        if self.rate <= 0.0 || self.types.is_empty() {
+            return Vec::new();
+        }
+        self.countdown -= seconds;
+        let mut due = Vec::new();
+        while self.countdown <= 0.0 && self.remaining != Some(0) {
+            due.push(-self.countdown);
+            self.countdown += 1.0 / self.rate;
+            self.remaining = self.remaining.map(|remaining| remaining - 1);
+        }
+        due
+    }
+}
+
+pub struct AsteroidStreamSystem;
+
+impl<'s> System<'s> for AsteroidStreamSystem {
+    type SystemData = (
+        WriteStorage<'s, AsteroidStream>,
+        ReadStorage<'s, Transform>,
+        Entities<'s>,
+        Read<'s, LazyUpdate>,
+        SpriteRes<'s>,
+        Read<'s, Materials>,
+        Write<'s, GameRng>,
+        Read<'s, Time>,
+    );
+
+    fn run(
+        &mut self,
+        (mut streams, transforms, entities, update, sprites, materials, mut rng, time): Self::SystemData,
+    ) {
+        for (stream, transform) in (&mut streams, &transforms).join() {
+            for late in stream.advance(time.delta_seconds()) {
+                let asteroid = stream.types[rng.gen_range(0..stream.types.len())].clone();
+                // Launches that fell due earlier in the frame have already travelled a way.
+                let mut transform = transform.clone();
+                transform.prepend_translation_x(stream.velocity.x * late);
+                transform.prepend_translation_y(stream.velocity.y * late);
+                generate_moving_asteroid(
+                    update.create_entity(&entities),
+                    sprites.get_handle(),
+                    &materials,
+                    asteroid,
+                    transform,
+                    stream.velocity,
+                    0.0,
+                );
+            }
+        }
+    }
+}
+
 pub struct AsteroidBundle;
 
 impl<'a, 'b> SystemBundle<'a, 'b> for AsteroidBundle {
@@ -500,6 +586,120 @@ impl<'a, 'b> SystemBundle<'a, 'b> for AsteroidBundle {
         dispatcher.add(AsteroidExplosionSystem::default(), "asteroid_explode", &[]);
         dispatcher.add(AsteroidReactionSystem::default(), "asteroid_react", &[]);
         dispatcher.add(ExplosionForceSystem, "explosion_force", &[]);
+        dispatcher.add(AsteroidStreamSystem, "asteroid_stream", &[]);
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{explosions::Explosion, test_support::TestWorldBuilder};
+
+    #[test]
+    fn touching_reactants_combine() {
+        let mut test = TestWorldBuilder::new()
+            .with(AsteroidReactionSystem::default(), "asteroid_react")
+            .build();
+        test.spawn_asteroid(AsteroidType::Hydrogen, (0.0, 0.0));
+        test.spawn_asteroid(AsteroidType::Oxygen, (4.0, 0.0));
+        test.run(3);
+        test.assert_asteroids(&[AsteroidType::Water]);
+    }
+
+    #[test]
+    fn distant_reactants_stay_apart() {
+        let mut test = TestWorldBuilder::new()
+            .with(AsteroidReactionSystem::default(), "asteroid_react")
+            .build();
+        test.spawn_asteroid(AsteroidType::Hydrogen, (0.0, 0.0));
+        test.spawn_asteroid(AsteroidType::Oxygen, (100.0, 0.0));
+        test.run(3);
+        test.assert_asteroids(&[AsteroidType::Hydrogen, AsteroidType::Oxygen]);
+    }
+
+    #[test]
+    fn touching_bombs_explode() {
+        let mut test = TestWorldBuilder::new()
+            .with(AsteroidExplosionSystem::default(), "asteroid_explode")
+            .build();
+        test.spawn_asteroid(AsteroidType::Bomb, (0.0, 0.0));
+        test.spawn_asteroid(AsteroidType::Bomb, (4.0, 0.0));
+        test.run(3);
+        test.assert_asteroids(&[]);
+        assert_eq!((&test.world.read_storage::<Explosion>()).join().count(), 1);
+    }
+
+    #[test]
+    fn streams_launch_at_their_rate_until_their_limit() {
+        let mut test = TestWorldBuilder::new()
+            .with(AsteroidStreamSystem, "asteroid_stream")
+            .build();
+        test.world.register::<Asteroid>();
+        test.world
+            .create_entity()
+            .with(Transform::default())
+            .with(AsteroidStream::new(
+                Vector2::new(0.0, 50.0),
+                25.0,
+                vec![AsteroidType::Small],
+                Some(3),
+            ))
+            .build();
+        test.run(4);
+        assert_eq!(test.asteroid_types().len(), 2);
+        test.run(30);
+        test.assert_asteroids(&[
+            AsteroidType::Small,
+            AsteroidType::Small,
+            AsteroidType::Small,
+        ]);
+    }
+
+    #[test]
+    fn streams_stagger_launches_due_in_the_same_frame() {
+        let mut stream = AsteroidStream::new(
+            Vector2::new(0.0, 50.0),
+            100.0,
+            vec![AsteroidType::Small],
+            None,
+        );
+        let due = stream.advance(0.025);
+        assert_eq!(due.len(), 3);
+        for (late, expected) in due.iter().zip([0.025, 0.015, 0.005]) {
+            assert!((late - expected).abs() < 0.0001, "{:?}", due);
+        }
+    }
+
+    #[test]
+    fn mixes_pick_in_proportion_to_their_weights() {
+        let mix = FieldMix::new(
+            vec![
+                (AsteroidType::Bomb, 3.0),
+                (AsteroidType::Sulphur, 1.0),
+                (AsteroidType::Artifact, 0.0),
+            ],
+            1000,
+        );
+        let mut rng = GameRng::new(Some(0));
+        let picks: Vec<_> = (0..1000).filter_map(|_| mix.pick(&mut rng)).collect();
+        let bombs = picks
+            .iter()
+            .filter(|asteroid| **asteroid == AsteroidType::Bomb)
+            .count();
+        assert_eq!(picks.len(), 1000);
+        assert!((700..800).contains(&bombs), "{} bombs", bombs);
+        assert!(!picks.contains(&AsteroidType::Artifact));
+        assert_eq!(
+            FieldMix::new(vec![(AsteroidType::Bomb, 0.0)], 1).pick(&mut rng),
+            None
+        );
+    }
+
+    #[test]
+    fn amounts_count_or_scale_with_area() {
+        assert_eq!(Amount::Count(7).count(2500.0), 7);
+        assert_eq!(Amount::Density(2.0).count(60_000.0), 12);
+        assert_eq!(FieldMix::preset(10, 0, 2, 0, 1, (0, 0.5)).len(), 3);
+    }
+}
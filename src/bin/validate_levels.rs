@@ -0,0 +1,84 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    process,
+};
+
+use amethyst::{renderer::sprite::Sprites, utils::application_root_dir};
+use serde::de::DeserializeOwned;
+
+use ld49::{level::Level, materials::Materials, scripting::LevelScript};
+
+fn load<T: DeserializeOwned>(path: &Path) -> Result<T, String> {
+    let bytes = fs::read(path).map_err(|error| error.to_string())?;
+    ron::de::from_bytes(&bytes).map_err(|error| error.to_string())
+}
+
+/// Checks `levels/levels.ron` and every level it lists, printing each problem found. Exits with a
+/// failure if there were any, so level changes can be gated on it.
+fn main() {
+    let assets_dir = std::env::args()
+        .nth(1)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| application_root_dir().unwrap().join("assets"));
+
+    let mut problems = Vec::new();
+    let sprite_count = match load::<Sprites>(&assets_dir.join("Sprites.ron")) {
+        Ok(Sprites::List(list)) => list.build_sprites().len(),
+        Ok(Sprites::Grid(grid)) => grid.build_sprites().len(),
+        Err(error) => {
+            problems.push(format!("Sprites.ron: {}", error));
+            0
+        }
+    };
+    let materials = load::<Materials>(&assets_dir.join("materials.ron")).unwrap_or_else(|error| {
+        problems.push(format!("materials.ron: {}", error));
+        Materials::default()
+    });
+    let level_paths =
+        load::<Vec<String>>(&assets_dir.join("levels/levels.ron")).unwrap_or_else(|error| {
+            problems.push(format!("levels/levels.ron: {}", error));
+            Vec::new()
+        });
+
+    let mut names: HashMap<String, String> = HashMap::new();
+    for path in level_paths.iter() {
+        let level = match load::<Level>(&assets_dir.join(path)) {
+            Ok(level) => level,
+            Err(error) => {
+                problems.push(format!("{}: {}", path, error));
+                continue;
+            }
+        };
+        match names.get(&level.reference.name) {
+            Some(first) => problems.push(format!(
+                "{}: name {:?} is already used by {}",
+                path, level.reference.name, first
+            )),
+            None => {
+                names.insert(level.reference.name.clone(), path.clone());
+            }
+        }
+        for problem in level.problems(sprite_count, &materials) {
+            problems.push(format!("{}: {}", path, problem));
+        }
+        if let Some(script) = &level.script {
+            if let Err(error) = LevelScript::load(&assets_dir, script) {
+                problems.push(format!("{}: script {}", path, error));
+            }
+        }
+    }
+
+    for problem in problems.iter() {
+        println!("{}", problem);
+    }
+    println!(
+        "Checked {} levels, found {} problems",
+        level_paths.len(),
+        problems.len()
+    );
+    if !problems.is_empty() {
+        process::exit(1);
+    }
+}
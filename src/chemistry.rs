@@ -0,0 +1,153 @@
+use std::collections::{BTreeMap, HashMap};
+
+use amethyst::{
+    assets::{Asset, Handle},
+    ecs::*,
+};
+
+use crate::asteroid::AsteroidType;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ReactionDesc {
+    pub reactants: (AsteroidType, AsteroidType),
+    pub products: (Option<AsteroidType>, Option<AsteroidType>),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ExplosionDesc {
+    pub reactants: (AsteroidType, AsteroidType),
+    pub strength: f32,
+    pub particles: Vec<usize>,
+}
+
+/// Rules are kept in name order, so when two match the same pair the first by name wins.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Chemistry {
+    reactions: BTreeMap<String, ReactionDesc>,
+    explosions: BTreeMap<String, ExplosionDesc>,
+}
+
+pub type ChemistryHandle = Handle<Chemistry>;
+
+impl Asset for Chemistry {
+    const NAME: &'static str = "ld49::Chemistry";
+    type Data = Chemistry;
+    type HandleStorage = VecStorage<ChemistryHandle>;
+}
+
+impl Chemistry {
+    /// Applies a level's overrides by name. `None` disables the named rule.
+    pub fn with_overrides(
+        mut self,
+        reactions: &Option<HashMap<String, Option<ReactionDesc>>>,
+        explosions: &Option<HashMap<String, Option<ExplosionDesc>>>,
+    ) -> Self {
+        if let Some(reactions) = reactions {
+            for (name, reaction) in reactions.iter() {
+                if let Some(reaction) = reaction {
+                    self.reactions.insert(name.clone(), reaction.clone());
+                } else {
+                    self.reactions.remove(name);
+                }
+            }
+        }
+        if let Some(explosions) = explosions {
+            for (name, explosion) in explosions.iter() {
+                if let Some(explosion) = explosion {
+                    self.explosions.insert(name.clone(), explosion.clone());
+                } else {
+                    self.explosions.remove(name);
+                }
+            }
+        }
+        self
+    }
+
+    pub fn explodes(&self, a: &AsteroidType, b: &AsteroidType) -> Option<(f32, Vec<usize>)> {
+        self.explosions
+            .values()
+            .find(|explosion| {
+                let reactants = (&explosion.reactants.0, &explosion.reactants.1);
+                reactants == (a, b) || reactants == (b, a)
+            })
+            .map(|explosion| (explosion.strength, explosion.particles.clone()))
+    }
+
+    pub fn reacts(
+        &self,
+        a: &AsteroidType,
+        b: &AsteroidType,
+    ) -> Option<(Option<AsteroidType>, Option<AsteroidType>)> {
+        self.reactions.values().find_map(|reaction| {
+            let reactants = (&reaction.reactants.0, &reaction.reactants.1);
+            if reactants == (a, b) {
+                Some(reaction.products.clone())
+            } else if reactants == (b, a) {
+                Some((reaction.products.1.clone(), reaction.products.0.clone()))
+            } else {
+                None
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::load_asset;
+    use AsteroidType::*;
+
+    #[test]
+    fn reactions_match_either_order() {
+        let chemistry: Chemistry = load_asset("reactions.ron");
+        assert_eq!(
+            chemistry.reacts(&Hydrogen, &Oxygen),
+            Some((Some(Water), None))
+        );
+        assert_eq!(
+            chemistry.reacts(&Oxygen, &Hydrogen),
+            Some((None, Some(Water)))
+        );
+        assert_eq!(chemistry.reacts(&Oxygen, &Oxygen), None);
+    }
+
+    #[test]
+    fn level_overrides_replace_and_disable_rules() {
+        let mut reactions = HashMap::new();
+        reactions.insert("water".to_string(), None);
+        reactions.insert(
+            "rust".to_string(),
+            Some(ReactionDesc {
+                reactants: (Water, Big),
+                products: (None, Some(Medium)),
+            }),
+        );
+        let mut explosions = HashMap::new();
+        explosions.insert("bomb".to_string(), None);
+        let chemistry = load_asset::<Chemistry>("reactions.ron")
+            .with_overrides(&Some(reactions), &Some(explosions));
+        assert_eq!(chemistry.reacts(&Hydrogen, &Oxygen), None);
+        assert_eq!(chemistry.reacts(&Big, &Water), Some((Some(Medium), None)));
+        assert!(chemistry.explodes(&Bomb, &Bomb).is_none());
+        assert!(chemistry.explodes(&Bomb, &Hydrogen).is_some());
+    }
+
+    #[test]
+    fn overlapping_rules_resolve_by_name() {
+        let mut reactions = HashMap::new();
+        for (name, product) in [("b_rust", Medium), ("a_rust", Small), ("c_rust", Bitty)] {
+            reactions.insert(
+                name.to_string(),
+                Some(ReactionDesc {
+                    reactants: (Water, Big),
+                    products: (None, Some(product)),
+                }),
+            );
+        }
+        let chemistry =
+            load_asset::<Chemistry>("reactions.ron").with_overrides(&Some(reactions), &None);
+        for _ in 0..10 {
+            assert_eq!(chemistry.reacts(&Water, &Big), Some((None, Some(Small))));
+        }
+    }
+}
@@ -1,22 +1,27 @@
 use amethyst::{
     core::{math::Vector3, Time, Transform},
     ecs::*,
-    input::{InputHandler, StringBindings},
     prelude::*,
     renderer::{sprite::SpriteSheetHandle, SpriteRender},
+    shrev::EventChannel,
 };
 use nalgebra::{Point2, Vector2};
 use ncollide2d::shape::{Ball, Cuboid, ShapeHandle};
 use nphysics2d::object::{BodyStatus, ColliderDesc, RigidBodyDesc};
+use rand::Rng;
 
 use crate::{
     assets::{SpriteHandles, SpriteRes, SpriteStorage},
     asteroid::Asteroid,
     economy::Enterprise,
+    events::{AsteroidDelivered, JumpStarted},
     level::Level,
+    materials::Materials,
     particles::{emit_particle, random_direction, Particle},
     physics::{Physics, PhysicsDesc, PhysicsHandle},
     player::Player,
+    replay::PlayerInput,
+    rng::GameRng,
 };
 
 #[derive(Component, Debug, Clone, Copy)]
@@ -152,25 +157,26 @@ impl<'s> System<'s> for DeliveryAnimationSystem {
         Read<'s, Time>,
         Entities<'s>,
         SpriteRes<'s>,
+        Write<'s, GameRng>,
     );
 
     fn run(
         &mut self,
-        (mut deliveries, transforms, update, time, entities, sprites): Self::SystemData,
+        (mut deliveries, transforms, update, time, entities, sprites, mut rng): Self::SystemData,
     ) {
         for (delivery, transform) in (&mut deliveries, &transforms).join() {
             delivery.cooldown = delivery.cooldown.and_then(|cooldown| {
                 if cooldown > time.delta_seconds() {
                     for (timestep, particle_chance) in DELIVERY_TIMESTEPS {
                         if cooldown < timestep {
-                            if rand::random::<f32>() > particle_chance {
+                            if rng.gen::<f32>() > particle_chance {
                                 let center_location = transform.translation();
-                                let mut direction = random_direction();
+                                let mut direction = random_direction(&mut *rng);
                                 direction /= f32::max(direction.x.abs(), direction.y.abs());
                                 emit_particle(
                                     update.create_entity(&entities),
                                     sprites.get_handle(),
-                                    Particle::delivery(direction),
+                                    Particle::delivery(direction, &mut *rng),
                                     Point2::new(
                                         center_location.x - direction.x * delivery.size.0 / 2.0,
                                         center_location.y - direction.y * delivery.size.1 / 2.0,
@@ -194,7 +200,7 @@ impl<'s> System<'s> for DeliveryAnimationSystem {
 pub struct PlayerDeliverySystem;
 impl<'s> System<'s> for PlayerDeliverySystem {
     type SystemData = (
-        Read<'s, InputHandler<StringBindings>>,
+        Read<'s, PlayerInput>,
         WriteStorage<'s, DeliveryZone>,
         Read<'s, Level>,
         Write<'s, Enterprise>,
@@ -202,24 +208,43 @@ impl<'s> System<'s> for PlayerDeliverySystem {
         ReadStorage<'s, Asteroid>,
         Entities<'s>,
         Write<'s, Physics>,
+        Read<'s, Materials>,
+        Write<'s, EventChannel<AsteroidDelivered>>,
     );
 
     fn run(
         &mut self,
-        (input, mut deliveries, level, mut enterprise, handles, asteroids, entities, physics): Self::SystemData,
+        (
+            input,
+            mut deliveries,
+            level,
+            mut enterprise,
+            handles,
+            asteroids,
+            entities,
+            physics,
+            materials,
+            mut delivered,
+        ): Self::SystemData,
     ) {
-        if input.action_is_down("deliver").unwrap_or(false) {
+        if input.deliver {
             for (delivery, delivery_handle) in (&mut deliveries, &handles).join() {
                 if delivery.cooldown.is_some() {
                     continue;
                 }
                 for (asteroid, handle, entity) in (&asteroids, &handles, &entities).join() {
                     if physics.is_intersecting(delivery_handle, handle) {
-                        enterprise.deliver(
-                            &level,
-                            asteroid.my_type,
-                            physics.get_mass(handle).unwrap_or(10.0),
+                        let mass = physics.get_mass(handle).unwrap_or(10.0);
+                        let income = enterprise.deliver(
+                            &asteroid.my_type,
+                            mass,
+                            level.get_ppm(&materials, &asteroid.my_type),
                         );
+                        delivered.single_write(AsteroidDelivered {
+                            asteroid: asteroid.my_type.clone(),
+                            mass,
+                            income,
+                        });
                         entities.delete(entity);
                         delivery.cooldown = Some(5.0);
                     }
@@ -229,10 +254,20 @@ impl<'s> System<'s> for PlayerDeliverySystem {
     }
 }
 
+/// Lets prices recover from what has been sold this level.
+pub struct MarketRecoverySystem;
+impl<'s> System<'s> for MarketRecoverySystem {
+    type SystemData = (Write<'s, Enterprise>, Read<'s, Time>);
+
+    fn run(&mut self, (mut enterprise, time): Self::SystemData) {
+        enterprise.market_mut().recover(time.delta_seconds());
+    }
+}
+
 pub struct PlayerJumpSystem;
 impl<'s> System<'s> for PlayerJumpSystem {
     type SystemData = (
-        Read<'s, InputHandler<StringBindings>>,
+        Read<'s, PlayerInput>,
         WriteStorage<'s, DeliveryZone>,
         Read<'s, Level>,
         Write<'s, Enterprise>,
@@ -240,22 +275,39 @@ impl<'s> System<'s> for PlayerJumpSystem {
         ReadStorage<'s, Player>,
         Entities<'s>,
         Write<'s, Physics>,
+        Write<'s, EventChannel<JumpStarted>>,
     );
 
     fn run(
         &mut self,
-        (input, mut deliveries, level, mut enterprise, handles, players, entities, mut physics): Self::SystemData,
+        (
+            input,
+            mut deliveries,
+            level,
+            mut enterprise,
+            handles,
+            players,
+            entities,
+            mut physics,
+            mut jumps,
+        ): Self::SystemData,
     ) {
-        if input.action_is_down("deliver").unwrap_or(false) {
-            for (delivery, delivery_handle) in (&mut deliveries, &handles).join() {
+        if input.deliver {
+            // One press is one jump, however many zones the level has.
+            'zones: for (delivery, delivery_handle) in (&mut deliveries, &handles).join() {
                 if delivery.cooldown.is_some() {
                     continue;
                 }
                 for (player, handle, entity) in (&players, &handles, &entities).join() {
-                    if enterprise.try_jump(&level) {
+                    if enterprise.try_jump(&level.reference.name, level.jump_cost) {
                         delivery.cooldown = Some(8.0);
                         delivery.jumping = true;
                         physics.set_static(handle);
+                        jumps.single_write(JumpStarted {
+                            level: level.reference.name.clone(),
+                            jump_cost: level.jump_cost,
+                        });
+                        break 'zones;
                     }
                 }
             }
@@ -357,3 +409,166 @@ fn render_arrow(
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        asteroid::AsteroidType,
+        level::{LevelTimer, LevelTimerSystem},
+        test_support::{level_from_ron, TestWorldBuilder},
+    };
+
+    fn delivery_test(level: Level) -> crate::test_support::TestWorld {
+        let mut test = TestWorldBuilder::new()
+            .with(PlayerDeliverySystem, "player_delivery")
+            .with_level(level)
+            .build();
+        test.spawn_delivery_zone((0.0, 0.0));
+        test.spawn_asteroid(AsteroidType::Medium, (10.0, 10.0));
+        test.run(2);
+        test
+    }
+
+    #[test]
+    fn delivering_sells_asteroids_in_the_zone() {
+        let mut test = delivery_test(Level::default());
+        test.hold(PlayerInput {
+            deliver: true,
+            ..PlayerInput::default()
+        });
+        test.run(2);
+        let medium = test
+            .world
+            .read_resource::<Materials>()
+            .get(&AsteroidType::Medium);
+        test.assert_asteroids(&[]);
+        assert_eq!(test.funds(), (medium.mass * medium.base_ppm) as u64);
+    }
+
+    #[test]
+    fn asteroids_stay_until_delivered() {
+        let mut test = delivery_test(Level::default());
+        test.run(2);
+        test.assert_asteroids(&[AsteroidType::Medium]);
+        assert_eq!(test.funds(), 0);
+    }
+
+    #[test]
+    fn levels_set_their_own_prices() {
+        let level = level_from_ron(
+            r#"(
+                boundaries: (500.0, 500.0),
+                deliveries: [(0.0, 0.0)],
+                jump_cost: 0,
+                card: (title: "Test", sprite_number: 0),
+                asteroids: [],
+                billboards: [],
+                modified_prices: Some({Medium: 10.0}),
+                reference: (name: "Test", description: "", shown_prices: []),
+            )"#,
+        );
+        let mut test = delivery_test(level);
+        test.hold(PlayerInput {
+            deliver: true,
+            ..PlayerInput::default()
+        });
+        test.run(2);
+        let medium = test
+            .world
+            .read_resource::<Materials>()
+            .get(&AsteroidType::Medium);
+        assert_eq!(test.funds(), (medium.mass * 10.0) as u64);
+    }
+
+    #[test]
+    fn deliveries_count_towards_contracts() {
+        let level = level_from_ron(
+            r#"(
+                boundaries: (500.0, 500.0),
+                deliveries: [(0.0, 0.0)],
+                jump_cost: 0,
+                card: (title: "Test", sprite_number: 0),
+                asteroids: [],
+                billboards: [],
+                reference: (name: "Test", description: "", shown_prices: []),
+                contracts: [(goal: Count(asteroid: Medium, count: 1), reward: 100)],
+            )"#,
+        );
+        let mut enterprise = Enterprise::begin_enterprise();
+        enterprise.arrive(&level.reference.name, &level.contracts);
+        let mut test = TestWorldBuilder::new()
+            .with(PlayerDeliverySystem, "player_delivery")
+            .with_level(level)
+            .with_enterprise(enterprise)
+            .build();
+        test.spawn_delivery_zone((0.0, 0.0));
+        test.spawn_asteroid(AsteroidType::Medium, (10.0, 10.0));
+        test.hold(PlayerInput {
+            deliver: true,
+            ..PlayerInput::default()
+        });
+        test.run(2);
+        let enterprise = test.world.read_resource::<Enterprise>();
+        assert!(enterprise.contracts()[0].met());
+    }
+
+    #[test]
+    fn jumping_stops_the_level_timer() {
+        let mut level = Level::default();
+        level.time_limit = Some(10.0);
+        let mut test = TestWorldBuilder::new()
+            .with(PlayerJumpSystem, "player_jump")
+            .with(LevelTimerSystem::default(), "level_timer")
+            .with_level(level)
+            .build();
+        test.spawn_delivery_zone((0.0, 0.0));
+        test.spawn_player((0.0, 0.0));
+        test.run(570);
+        test.hold(PlayerInput {
+            deliver: true,
+            ..PlayerInput::default()
+        });
+        // Longer than the jump animation, and than the time left.
+        test.run(600);
+        let level = test.world.read_resource::<Level>();
+        let timer = test.world.read_resource::<LevelTimer>();
+        assert!(timer.stopped);
+        assert!(!timer.expired(&level));
+    }
+
+    #[test]
+    fn one_press_jumps_once_from_two_zones() {
+        let mut test = TestWorldBuilder::new()
+            .with(PlayerJumpSystem, "player_jump")
+            .with_level(Level::default())
+            .build();
+        let mut jumps = test
+            .world
+            .write_resource::<EventChannel<JumpStarted>>()
+            .register_reader();
+        test.spawn_delivery_zone((-50.0, 0.0));
+        test.spawn_delivery_zone((50.0, 0.0));
+        test.spawn_player((0.0, 0.0));
+        test.hold(PlayerInput {
+            deliver: true,
+            ..PlayerInput::default()
+        });
+        test.run(1);
+        assert_eq!(
+            test.world
+                .read_resource::<EventChannel<JumpStarted>>()
+                .read(&mut jumps)
+                .count(),
+            1
+        );
+        let deliveries = test.world.read_storage::<DeliveryZone>();
+        assert_eq!(
+            (&deliveries)
+                .join()
+                .filter(|delivery| delivery.jumping)
+                .count(),
+            1
+        );
+    }
+}
@@ -0,0 +1,32 @@
+//! Development mode, turned on with `--dev`. Assets loaded through the asset system are reloaded
+//! when their files change, and F5 restarts the level in progress so it's rebuilt from them.
+
+use amethyst::{
+    assets::{HotReloadBundle, HotReloadStrategy},
+    input::{is_key_down, VirtualKeyCode},
+    prelude::*,
+    winit::Event,
+};
+
+use crate::replay::ReplayMode;
+
+/// How many frames go by between checks for changed asset files.
+const RELOAD_FRAMES: u8 = 30;
+
+#[derive(Default, Clone, Copy)]
+pub struct DevMode(pub bool);
+
+/// Watches the assets directory for changes to anything loaded through the `Loader`: levels,
+/// reactions, materials and UI.
+pub fn hot_reload_bundle() -> HotReloadBundle {
+    HotReloadBundle::new(HotReloadStrategy::every(RELOAD_FRAMES))
+}
+
+/// Restarting is left alone while recording or playing back, since it would throw the replay off.
+pub fn restart_requested(world: &World, event: &Event) -> bool {
+    world.try_fetch::<DevMode>().map_or(false, |dev| dev.0)
+        && world
+            .try_fetch::<ReplayMode>()
+            .map_or(true, |replay| matches!(*replay, ReplayMode::Live))
+        && is_key_down(event, VirtualKeyCode::F5)
+}
@@ -1,30 +1,336 @@
 use std::collections::HashMap;
 
-use amethyst::{
-    core::{HiddenPropagate, SystemBundle, Time, Transform},
-    ecs::*,
-    prelude::*,
-    renderer::{sprite::SpriteSheetHandle, SpriteRender},
-    ui::{UiFinder, UiImage, UiText, UiTransform},
-    Error,
-};
-
-use crate::{
-    assets::{SpriteHandles, SpriteRes},
-    asteroid::AsteroidType,
-    level::{Level, LevelHandle},
-    menu::find_by_id,
-};
+use rand::{seq::SliceRandom, Rng};
+
+use crate::asteroid::AsteroidType;
+
+/// What a unit of fuel costs when a level doesn't set its own price.
+pub const DEFAULT_FUEL_PRICE: f64 = 10.0;
+/// Lent out whenever refueling costs more than the enterprise has.
+pub const LOAN_AMOUNT: u64 = 2000;
+/// Added to the debt, as a share of it, every time a level is completed.
+pub const INTEREST_RATE: f64 = 0.05;
+/// The share of each delivery's income that goes straight to paying off debt.
+pub const REPAYMENT_SHARE: f64 = 0.25;
+/// Owing more than this is bankruptcy.
+pub const DEBT_CEILING: u64 = 10000;
+const FULL_TANK: f64 = 100.0;
+
+/// How far one unit of delivered mass pushes its type's price down.
+const SATURATION_PER_MASS: f32 = 0.005;
+/// Deliveries never push a price below this share of what it would otherwise be.
+const MAX_SATURATION: f32 = 0.6;
+/// How much of the saturation wears off every second.
+const RECOVERY_PER_SECOND: f32 = 0.01;
+/// The chance of some news moving the market between levels.
+const MARKET_EVENT_CHANCE: f64 = 0.5;
+/// What market news can do to a price.
+const MARKET_EVENT_FACTORS: [f32; 4] = [0.6, 0.8, 1.25, 1.5];
+
+/// A change in a commodity's price, announced between levels.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MarketEvent {
+    pub asteroid: AsteroidType,
+    pub factor: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Trend {
+    Rising,
+    Steady,
+    Falling,
+}
+
+/// Supply and demand on top of the level's prices. Selling a type floods the market for it until
+/// the end of the level, though it slowly recovers; news between levels moves prices for longer.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Market {
+    /// Price multipliers left by market news, drifting back to 1 over a few levels.
+    #[serde(default)]
+    trends: HashMap<AsteroidType, f32>,
+    /// How far deliveries this level have pushed each type's price down.
+    #[serde(default)]
+    saturation: HashMap<AsteroidType, f32>,
+    #[serde(default)]
+    news: Option<MarketEvent>,
+}
+
+impl Market {
+    fn multiplier(&self, asteroid: &AsteroidType) -> f32 {
+        let trend = self.trends.get(asteroid).cloned().unwrap_or(1.0);
+        let saturation = self.saturation.get(asteroid).cloned().unwrap_or(0.0);
+        trend * (1.0 - saturation)
+    }
+
+    /// What a type sells for right now, given the level's price for it.
+    pub fn price(&self, asteroid: &AsteroidType, level_price: f32) -> f32 {
+        level_price * self.multiplier(asteroid)
+    }
+
+    pub fn trend(&self, asteroid: &AsteroidType) -> Trend {
+        let multiplier = self.multiplier(asteroid);
+        if multiplier > 1.05 {
+            Trend::Rising
+        } else if multiplier < 0.95 {
+            Trend::Falling
+        } else {
+            Trend::Steady
+        }
+    }
+
+    /// The news from the last time levels changed, if there was any.
+    pub fn news(&self) -> Option<&MarketEvent> {
+        self.news.as_ref()
+    }
+
+    fn sold(&mut self, asteroid: &AsteroidType, mass: f32) {
+        let saturation = self.saturation.entry(asteroid.clone()).or_insert(0.0);
+        *saturation = f32::min(MAX_SATURATION, *saturation + mass * SATURATION_PER_MASS);
+    }
+
+    pub fn recover(&mut self, seconds: f32) {
+        for saturation in self.saturation.values_mut() {
+            *saturation = f32::max(0.0, *saturation - RECOVERY_PER_SECOND * seconds);
+        }
+        self.saturation.retain(|_, saturation| *saturation > 0.0);
+    }
+
+    /// Moves the market between levels: old news fades and, sometimes, there's new news about
+    /// one of `asteroids`.
+    pub fn shake<R: Rng>(&mut self, rng: &mut R, asteroids: &[AsteroidType]) {
+        self.saturation.clear();
+        for trend in self.trends.values_mut() {
+            *trend += (1.0 - *trend) / 3.0;
+        }
+        self.trends.retain(|_, trend| (*trend - 1.0).abs() > 0.01);
+        self.news = None;
+        if rng.gen_bool(MARKET_EVENT_CHANCE) {
+            if let (Some(asteroid), Some(factor)) = (
+                asteroids.choose(rng),
+                MARKET_EVENT_FACTORS.choose(rng).cloned(),
+            ) {
+                let trend = self.trends.entry(asteroid.clone()).or_insert(1.0);
+                *trend = (*trend * factor).clamp(0.25, 4.0);
+                self.news = Some(MarketEvent {
+                    asteroid: asteroid.clone(),
+                    factor,
+                });
+            }
+        }
+    }
+}
+
+/// What a contract asks of the deliveries made during a level.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum ContractGoal {
+    Count {
+        asteroid: AsteroidType,
+        count: usize,
+    },
+    Mass {
+        asteroid: AsteroidType,
+        mass: f32,
+    },
+    /// Met as long as none of this type are delivered.
+    Avoid {
+        asteroid: AsteroidType,
+    },
+}
+
+/// A level's objective, settled when the player jumps out: paid the reward if its goal was met,
+/// charged the penalty if not.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Contract {
+    pub goal: ContractGoal,
+    #[serde(default)]
+    pub reward: u64,
+    #[serde(default)]
+    pub penalty: u64,
+}
+
+impl Contract {
+    pub fn asteroid(&self) -> &AsteroidType {
+        match &self.goal {
+            ContractGoal::Count { asteroid, .. }
+            | ContractGoal::Mass { asteroid, .. }
+            | ContractGoal::Avoid { asteroid } => asteroid,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match &self.goal {
+            ContractGoal::Count { asteroid, count } => format!("Deliver {} {:?}", count, asteroid),
+            ContractGoal::Mass { asteroid, mass } => {
+                format!("Deliver {:.0} mass of {:?}", mass, asteroid)
+            }
+            ContractGoal::Avoid { asteroid } => format!("Deliver no {:?}", asteroid),
+        }
+    }
+}
+
+/// A contract taken on for the level in progress, with what has been delivered towards it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ContractProgress {
+    pub contract: Contract,
+    pub count: usize,
+    pub mass: f32,
+}
+
+impl ContractProgress {
+    fn new(contract: Contract) -> Self {
+        ContractProgress {
+            contract,
+            count: 0,
+            mass: 0.0,
+        }
+    }
+
+    pub fn met(&self) -> bool {
+        match &self.contract.goal {
+            ContractGoal::Count { count, .. } => self.count >= *count,
+            ContractGoal::Mass { mass, .. } => self.mass >= *mass,
+            ContractGoal::Avoid { .. } => self.count == 0,
+        }
+    }
+
+    /// How far along the contract is, for the HUD.
+    pub fn describe(&self) -> String {
+        let progress = match &self.contract.goal {
+            ContractGoal::Count { count, .. } => format!("{}/{}", self.count, count),
+            ContractGoal::Mass { mass, .. } => format!("{:.0}/{:.0}", self.mass, mass),
+            ContractGoal::Avoid { .. } if self.met() => "kept".to_string(),
+            ContractGoal::Avoid { .. } => "broken".to_string(),
+        };
+        format!("{}: {}", self.contract.describe(), progress)
+    }
+}
+
+/// Points for each unit of fuel left in the tank at the end of a level.
+const SCORE_PER_FUEL: f64 = 10.0;
+const SCORE_PER_CONTRACT: u64 = 250;
+
+/// Rates a completed level on its earnings, the fuel left and the contracts met. Finishing under
+/// par multiplies that, up to double; going over par shrinks it, down to half.
+pub fn score(summary: &LevelSummary, fuel_left: f64, elapsed: f32, par_time: Option<f32>) -> u64 {
+    let earnings =
+        (summary.income + summary.contract_rewards).saturating_sub(summary.contract_penalties);
+    let base = earnings
+        + (f64::max(0.0, fuel_left) * SCORE_PER_FUEL) as u64
+        + summary.contracts_met as u64 * SCORE_PER_CONTRACT;
+    let pace = match par_time {
+        Some(par_time) if elapsed > 0.0 => (par_time / elapsed).clamp(0.5, 2.0),
+        _ => 1.0,
+    };
+    (base as f64 * pace as f64) as u64
+}
+
+/// One change to an enterprise's books.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum Transaction {
+    /// Marks the start of a level, so its entries can be told apart from the last one's. Only the
+    /// previous level's entries are kept past one.
+    Arrival {
+        level: String,
+    },
+    Delivery {
+        asteroid: AsteroidType,
+        mass: f32,
+        income: u64,
+    },
+    /// Fuel burned in a row is kept as a single entry.
+    FuelBurn {
+        fuel: f64,
+    },
+    Refuel {
+        fuel: f64,
+        cost: u64,
+    },
+    Loan {
+        amount: u64,
+    },
+    Interest {
+        amount: u64,
+    },
+    Repayment {
+        amount: u64,
+    },
+    JumpFee {
+        level: String,
+        cost: u64,
+    },
+    ContractReward {
+        amount: u64,
+    },
+    /// Paid out by a level script. Counts as income.
+    Bonus {
+        amount: u64,
+    },
+    /// Whatever the funds can't cover is added to the debt.
+    ContractPenalty {
+        amount: u64,
+    },
+    /// Wipes the enterprise clean. Everything before it is dropped from the ledger.
+    Bankruptcy {
+        debt: u64,
+    },
+}
+
+/// When a level is offered, counted in levels the enterprise has completed.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Unlock {
+    /// Offered once this many levels have been completed...
+    #[serde(default)]
+    pub after: usize,
+    /// ...and no longer once this many have.
+    #[serde(default)]
+    pub until: Option<usize>,
+    /// Held back until this many other levels have been completed since it was last.
+    #[serde(default)]
+    pub rest: usize,
+}
+
+/// The books for the level in progress, or the one just finished.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LevelSummary {
+    pub income: u64,
+    pub fuel_burned: f64,
+    pub refueling: u64,
+    pub jump_fee: u64,
+    pub loans: u64,
+    pub interest: u64,
+    pub repaid: u64,
+    pub contract_rewards: u64,
+    pub contract_penalties: u64,
+    pub contracts_met: usize,
+    pub loans_taken: usize,
+}
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Enterprise {
-    pub fuel: f64,
+    fuel: f64,
     funds: u64,
     loans: i32,
+    #[serde(default)]
+    debt: u64,
     bankruptcies: usize,
     tried_jump: Option<f32>,
-    last_refueling: (u64, bool),
-    last_completions: Vec<String>,
+    #[serde(default, alias = "last_completions")]
+    completed: Vec<String>,
+    #[serde(default)]
+    ledger: Vec<Transaction>,
+    #[serde(default)]
+    market: Market,
+    #[serde(default)]
+    contracts: Vec<ContractProgress>,
+    /// The best score for each level, by name.
+    #[serde(default)]
+    best_scores: HashMap<String, u64>,
+    /// The score for the level just finished, if it was completed.
+    #[serde(default)]
+    latest_score: Option<u64>,
+    /// Picks the uncharted sectors offered, so they don't change between visits to the menu.
+    #[serde(default = "rand::random")]
+    seed: u64,
 }
 
 impl Default for Enterprise {
@@ -36,237 +342,562 @@ impl Default for Enterprise {
 impl Enterprise {
     pub fn begin_enterprise() -> Self {
         Enterprise {
-            fuel: 100.0,
+            fuel: FULL_TANK,
             funds: 0,
             loans: 0,
+            debt: 0,
             bankruptcies: 0,
             tried_jump: None,
-            last_refueling: (0, false),
-            last_completions: Vec::new(),
+            completed: Vec::new(),
+            ledger: Vec::new(),
+            market: Market::default(),
+            contracts: Vec::new(),
+            best_scores: HashMap::new(),
+            latest_score: None,
+            seed: rand::random(),
         }
     }
 
-    pub fn deliver(&mut self, level: &Level, asteroid: AsteroidType, mass: f32) {
-        let ppm = level.get_ppm(asteroid);
-        self.funds += (mass * ppm) as u64;
+    pub fn funds(&self) -> u64 {
+        self.funds
     }
 
-    pub fn eat_fuel(&mut self, rate: f64, time: &Time) {
-        self.fuel -= rate * (time.delta_seconds() as f64);
+    pub fn fuel(&self) -> f64 {
+        self.fuel
     }
 
-    pub fn burn_fuel(&mut self, burn: f64) {
-        self.fuel -= burn;
+    pub fn debt(&self) -> u64 {
+        self.debt
     }
 
-    pub fn try_jump(&mut self, level: &Level) -> bool {
-        if !self.can_jump(level) {
-            self.tried_jump = Some(3.5);
-            false
-        } else {
-            self.funds -= level.jump_cost;
-            self.last_completions.push(level.reference.name.clone());
-            self.last_completions.truncate(6);
-            true
-        }
+    /// Whether a loan has been taken since the enterprise began, even one long since repaid.
+    pub fn ever_borrowed(&self) -> bool {
+        self.loans > 0
     }
 
-    pub fn refueling_cost(&self) -> u64 {
-        return f64::max(0.0, (100.0 - self.fuel) * 10.0) as u64;
+    pub fn bankruptcies(&self) -> usize {
+        self.bankruptcies
     }
 
-    pub fn refuel(&mut self) {
-        let fuel_costs = self.refueling_cost();
-        self.last_refueling = (fuel_costs, fuel_costs > self.funds);
-        if self.last_refueling.1 {
-            self.loans += 1;
-            self.funds += 2000;
-        }
-        self.funds = self.funds - fuel_costs;
-        self.fuel = 100.0;
+    pub fn market(&self) -> &Market {
+        &self.market
     }
 
-    pub fn can_jump(&mut self, level: &Level) -> bool {
-        self.funds >= level.jump_cost
+    pub fn market_mut(&mut self) -> &mut Market {
+        &mut self.market
     }
 
-    pub fn get_next_levels(&self, levels: Vec<(Level, LevelHandle)>) -> Vec<(Level, LevelHandle)> {
-        if self.last_completions.len() == 0 {
-            levels
-                .iter()
-                .filter(|(level, handle)| level.reference.name == "Tutorial")
-                .cloned()
-                .collect()
-        } else if self.last_completions.len() == 1 {
-            levels
-                .iter()
-                .filter(|(level, handle)| level.reference.name != "Tutorial")
-                .filter(|(level, handle)| level.reference.name == "Striking Out!")
-                .cloned()
-                .collect()
-        } else {
-            levels
-                .iter()
-                .filter(|(level, handle)| level.reference.name != "Tutorial")
-                .filter(|(level, handle)| level.reference.name != "Striking Out!")
-                .filter(|(level, handle)| !self.last_completions.contains(&level.reference.name))
-                .cloned()
-                .collect()
+    /// The contracts for the level in progress.
+    pub fn contracts(&self) -> &[ContractProgress] {
+        &self.contracts
+    }
+
+    pub fn ledger(&self) -> &[Transaction] {
+        &self.ledger
+    }
+
+    /// Every change to funds and fuel goes through here and onto the ledger.
+    pub fn apply(&mut self, transaction: Transaction) {
+        match &transaction {
+            Transaction::Arrival { .. } => {
+                self.market.saturation.clear();
+                if let Some(previous) = self
+                    .ledger
+                    .iter()
+                    .rposition(|transaction| matches!(transaction, Transaction::Arrival { .. }))
+                {
+                    self.ledger.drain(..previous);
+                }
+            }
+            Transaction::Delivery {
+                asteroid,
+                mass,
+                income,
+            } => {
+                self.funds += income;
+                for progress in self.contracts.iter_mut() {
+                    if progress.contract.asteroid() == asteroid {
+                        progress.count += 1;
+                        progress.mass += mass;
+                    }
+                }
+            }
+            Transaction::FuelBurn { fuel } => {
+                self.fuel -= fuel;
+                if let Some(Transaction::FuelBurn { fuel: burned }) = self.ledger.last_mut() {
+                    *burned += fuel;
+                    return;
+                }
+            }
+            Transaction::Refuel { fuel, cost } => {
+                self.fuel += fuel;
+                self.funds -= cost;
+            }
+            Transaction::Loan { amount } => {
+                self.loans += 1;
+                self.funds += amount;
+                self.debt += amount;
+            }
+            Transaction::Interest { amount } => self.debt += amount,
+            Transaction::Repayment { amount } => {
+                self.funds -= amount;
+                self.debt -= amount;
+            }
+            Transaction::JumpFee { level, cost } => {
+                self.funds -= cost;
+                self.completed.push(level.clone());
+            }
+            Transaction::ContractReward { amount } | Transaction::Bonus { amount } => {
+                self.funds += amount
+            }
+            Transaction::ContractPenalty { amount } => {
+                let paid = u64::min(self.funds, *amount);
+                self.funds -= paid;
+                self.debt += amount - paid;
+            }
+            Transaction::Bankruptcy { .. } => {
+                *self = Enterprise {
+                    bankruptcies: self.bankruptcies + 1,
+                    market: self.market.clone(),
+                    best_scores: self.best_scores.clone(),
+                    ..Enterprise::begin_enterprise()
+                };
+            }
         }
+        self.ledger.push(transaction);
     }
-}
 
-pub struct MoneyHudSystem;
-impl MoneyHudSystem {
-    fn symbol<'s>(sprites: &SpriteRes<'s>) -> UiImage {
-        UiImage::Sprite(SpriteRender {
-            sprite_sheet: sprites.get_handle(),
-            sprite_number: 26,
-        })
+    /// Starts a level, taking on its contracts.
+    pub fn arrive(&mut self, level: &str, contracts: &[Contract]) {
+        self.apply(Transaction::Arrival {
+            level: level.to_string(),
+        });
+        self.contracts = contracts
+            .iter()
+            .cloned()
+            .map(ContractProgress::new)
+            .collect();
+        self.latest_score = None;
+    }
+
+    /// Keeps the score for a completed level, returning whether it is the best there so far.
+    pub fn record_score(&mut self, level: &str, score: u64) -> bool {
+        self.latest_score = Some(score);
+        let best = self.best_scores.entry(level.to_string()).or_insert(0);
+        let new_best = score > *best;
+        *best = u64::max(*best, score);
+        new_best
+    }
+
+    pub fn latest_score(&self) -> Option<u64> {
+        self.latest_score
     }
 
-    fn digit<'s>(sprites: &SpriteRes<'s>, idx: usize, value: u64) -> UiImage {
-        let place = (10 as u64).pow(idx as u32);
-        let mut digit = 16 + ((value / place) % 10);
-        if value < place {
-            digit = 15;
+    pub fn best_score(&self, level: &str) -> Option<u64> {
+        self.best_scores.get(level).cloned()
+    }
+
+    /// Sells an asteroid at the market's price for it, putting part of the income towards any debt.
+    pub fn deliver(&mut self, asteroid: &AsteroidType, mass: f32, level_price: f32) -> u64 {
+        let income = (mass * self.market.price(asteroid, level_price)) as u64;
+        self.market.sold(asteroid, mass);
+        self.apply(Transaction::Delivery {
+            asteroid: asteroid.clone(),
+            mass,
+            income,
+        });
+        let amount = u64::min(self.debt, (income as f64 * REPAYMENT_SHARE) as u64);
+        if amount > 0 {
+            self.apply(Transaction::Repayment { amount });
         }
-        UiImage::Sprite(SpriteRender {
-            sprite_sheet: sprites.get_handle(),
-            sprite_number: digit as usize,
-        })
+        income
     }
 
-    fn insufficient_funds<'s>(sprites: &SpriteRes<'s>, insufficient: bool) -> UiImage {
-        if insufficient {
-            UiImage::Sprite(SpriteRender {
-                sprite_sheet: sprites.get_handle(),
-                sprite_number: 30,
-            })
-        } else {
-            UiImage::SolidColor([0.0, 0.0, 0.0, 0.0])
+    /// Pays off as much debt as the funds allow, returning how much that was.
+    pub fn repay(&mut self) -> u64 {
+        let amount = u64::min(self.debt, self.funds);
+        if amount > 0 {
+            self.apply(Transaction::Repayment { amount });
         }
+        amount
+    }
+
+    pub fn eat_fuel(&mut self, rate: f64, seconds: f64) {
+        self.burn_fuel(rate * seconds);
+    }
+
+    pub fn burn_fuel(&mut self, burn: f64) {
+        self.apply(Transaction::FuelBurn { fuel: burn });
     }
 
-    fn sufficient_funds<'s>(sprites: &SpriteRes<'s>, sufficient: bool) -> UiImage {
-        if sufficient {
-            UiImage::Sprite(SpriteRender {
-                sprite_sheet: sprites.get_handle(),
-                sprite_number: 31,
-            })
+    pub fn try_jump(&mut self, level: &str, jump_cost: u64) -> bool {
+        if !self.can_jump(jump_cost) {
+            self.tried_jump = Some(3.5);
+            false
         } else {
-            UiImage::SolidColor([0.0, 0.0, 0.0, 0.0])
+            self.apply(Transaction::JumpFee {
+                level: level.to_string(),
+                cost: jump_cost,
+            });
+            self.settle_contracts();
+            let amount = (self.debt as f64 * INTEREST_RATE).ceil() as u64;
+            if amount > 0 {
+                self.apply(Transaction::Interest { amount });
+            }
+            true
         }
     }
-}
-impl<'s> System<'s> for MoneyHudSystem {
-    type SystemData = (
-        Entities<'s>,
-        WriteStorage<'s, UiTransform>,
-        WriteStorage<'s, UiImage>,
-        WriteStorage<'s, UiText>,
-        WriteStorage<'s, HiddenPropagate>,
-        SpriteRes<'s>,
-        Read<'s, Level>,
-        Write<'s, Enterprise>,
-    );
-
-    fn run(
-        &mut self,
-        (
-            entities,
-            mut transforms,
-            mut images,
-            mut texts,
-            mut hiddens,
-            sprites,
-            level,
-            mut enterprise,
-        ): Self::SystemData,
-    ) {
-        if let Some(symbol) = find_by_id(&entities, &transforms, "insufficient_funds") {
-            images.insert(
-                symbol,
-                MoneyHudSystem::insufficient_funds(&sprites, enterprise.tried_jump.is_some()),
-            );
-        }
-        if let Some(symbol) = find_by_id(&entities, &transforms, "sufficient_funds") {
-            images.insert(
-                symbol,
-                MoneyHudSystem::sufficient_funds(&sprites, enterprise.can_jump(&level)),
-            );
-        }
-        if let Some(refueling) = find_by_id(&entities, &transforms, "refueling") {
-            if let Some(refueling_text) = texts.get_mut(refueling) {
-                refueling_text.text =
-                    format!("Your refueling costs: {}", enterprise.last_refueling.0);
+
+    fn settle_contracts(&mut self) {
+        for progress in std::mem::take(&mut self.contracts) {
+            // Met contracts are always recorded, even unpaid, so they count towards the score.
+            if progress.met() {
+                self.apply(Transaction::ContractReward {
+                    amount: progress.contract.reward,
+                });
+            } else if progress.contract.penalty > 0 {
+                self.apply(Transaction::ContractPenalty {
+                    amount: progress.contract.penalty,
+                });
             }
         }
-        if let Some(loan) = find_by_id(&entities, &transforms, "loan") {
-            if enterprise.last_refueling.1 {
-                hiddens.remove(loan);
+    }
+
+    pub fn can_jump(&self, jump_cost: u64) -> bool {
+        self.funds >= jump_cost
+    }
+
+    pub fn tried_jump(&self) -> bool {
+        self.tried_jump.is_some()
+    }
+
+    /// Counts down the warning shown after trying to jump without the funds.
+    pub fn cool_down_warning(&mut self, seconds: f32) {
+        self.tried_jump = self.tried_jump.and_then(|warning_lifetime| {
+            if warning_lifetime > seconds {
+                Some(warning_lifetime - seconds)
             } else {
-                hiddens.insert(loan, HiddenPropagate::new());
-            }
-        }
-        if let Some(fuel_cost) = find_by_id(&entities, &transforms, "fuel_cost") {
-            if let Some(fuel_cost) = texts.get_mut(fuel_cost) {
-                fuel_cost.text = format!(
-                    "Cost to jump: {} - Current fuel cost: {}",
-                    level.jump_cost,
-                    enterprise.refueling_cost()
-                );
+                None
             }
+        })
+    }
+
+    pub fn refueling_cost(&self, fuel_price: f64) -> u64 {
+        f64::max(0.0, (FULL_TANK - self.fuel) * fuel_price) as u64
+    }
+
+    /// Fills the tank, borrowing as much as it takes to cover it.
+    pub fn refuel(&mut self, fuel_price: f64) {
+        let cost = self.refueling_cost(fuel_price);
+        while cost > self.funds {
+            self.apply(Transaction::Loan {
+                amount: LOAN_AMOUNT,
+            });
         }
-        if let Some(symbol) = find_by_id(&entities, &transforms, "money_symbol") {
-            images.insert(symbol, MoneyHudSystem::symbol(&sprites));
-        }
-        if let Some(fuel_level) = find_by_id(&entities, &transforms, "fuel_value") {
-            if let Some(fuel_level) = transforms.get_mut(fuel_level) {
-                fuel_level.width = (180.0 * enterprise.fuel / 100.0) as f32;
-            }
+        self.apply(Transaction::Refuel {
+            fuel: FULL_TANK - self.fuel,
+            cost,
+        });
+    }
+
+    pub fn bankrupt(&self) -> bool {
+        self.debt > DEBT_CEILING
+    }
+
+    /// Starts over with a clean slate, keeping count of how often that has happened.
+    pub fn declare_bankruptcy(&mut self) {
+        self.apply(Transaction::Bankruptcy { debt: self.debt });
+    }
+
+    /// Saves from before debt was tracked only counted loans, each of which was `LOAN_AMOUNT`.
+    pub(crate) fn count_old_loans_as_debt(&mut self) {
+        if self.debt == 0 {
+            self.debt = self.loans.max(0) as u64 * LOAN_AMOUNT;
         }
-        for idx in 0..11 {
-            if let Some(digit) =
-                find_by_id(&entities, &transforms, format!("money_{}", idx).as_ref())
-            {
-                images.insert(
-                    digit,
-                    MoneyHudSystem::digit(&sprites, idx, enterprise.funds),
-                );
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn levels_completed(&self) -> usize {
+        self.completed.len()
+    }
+
+    /// Whether the level menu should offer a level with these unlock rules.
+    pub fn offers(&self, level: &str, unlock: &Unlock) -> bool {
+        let completed = self.completed.len();
+        completed >= unlock.after
+            && unlock.until.map_or(true, |until| completed < until)
+            && !self
+                .completed
+                .iter()
+                .rev()
+                .take(unlock.rest)
+                .any(|name| name == level)
+    }
+
+    /// Totals the ledger since the latest arrival.
+    pub fn level_summary(&self) -> LevelSummary {
+        let start = self
+            .ledger
+            .iter()
+            .rposition(|transaction| matches!(transaction, Transaction::Arrival { .. }))
+            .unwrap_or(0);
+        let mut summary = LevelSummary::default();
+        for transaction in self.ledger[start..].iter() {
+            match transaction {
+                Transaction::Delivery { income, .. } | Transaction::Bonus { amount: income } => {
+                    summary.income += income
+                }
+                Transaction::FuelBurn { fuel } => summary.fuel_burned += fuel,
+                Transaction::Refuel { cost, .. } => summary.refueling += cost,
+                Transaction::Loan { amount } => {
+                    summary.loans += amount;
+                    summary.loans_taken += 1;
+                }
+                Transaction::Interest { amount } => summary.interest += amount,
+                Transaction::Repayment { amount } => summary.repaid += amount,
+                Transaction::JumpFee { cost, .. } => summary.jump_fee += cost,
+                Transaction::ContractReward { amount } => {
+                    summary.contract_rewards += amount;
+                    summary.contracts_met += 1;
+                }
+                Transaction::ContractPenalty { amount } => summary.contract_penalties += amount,
+                Transaction::Arrival { .. } | Transaction::Bankruptcy { .. } => {}
             }
         }
+        summary
     }
 }
 
-pub struct InsufficientFundsWarningSystem;
-impl<'s> System<'s> for InsufficientFundsWarningSystem {
-    type SystemData = (Write<'s, Enterprise>, Read<'s, Time>);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    fn run(&mut self, (mut enterprise, time): Self::SystemData) {
-        enterprise.tried_jump = enterprise.tried_jump.and_then(|warning_lifetime| {
-            if warning_lifetime > time.delta_seconds() {
-                Some(warning_lifetime - time.delta_seconds())
-            } else {
-                None
+    #[test]
+    fn deliveries_are_paid_and_recorded() {
+        let mut enterprise = Enterprise::begin_enterprise();
+        enterprise.deliver(&AsteroidType::Medium, 10.0, 2.5);
+        assert_eq!(enterprise.funds(), 25);
+        assert_eq!(
+            enterprise.ledger(),
+            &[Transaction::Delivery {
+                asteroid: AsteroidType::Medium,
+                mass: 10.0,
+                income: 25,
+            }]
+        );
+    }
+
+    #[test]
+    fn fuel_burned_in_a_row_is_one_entry() {
+        let mut enterprise = Enterprise::begin_enterprise();
+        enterprise.eat_fuel(1.0, 0.5);
+        enterprise.burn_fuel(2.0);
+        assert_eq!(enterprise.fuel(), 97.5);
+        assert_eq!(enterprise.ledger(), &[Transaction::FuelBurn { fuel: 2.5 }]);
+    }
+
+    #[test]
+    fn refueling_borrows_when_short() {
+        let mut enterprise = Enterprise::begin_enterprise();
+        enterprise.burn_fuel(50.0);
+        enterprise.refuel(DEFAULT_FUEL_PRICE);
+        assert_eq!(enterprise.fuel(), 100.0);
+        assert_eq!(enterprise.funds(), LOAN_AMOUNT - 500);
+        let summary = enterprise.level_summary();
+        assert_eq!(summary.refueling, 500);
+        assert_eq!(summary.loans, LOAN_AMOUNT);
+        assert_eq!(enterprise.debt(), LOAN_AMOUNT);
+    }
+
+    #[test]
+    fn debt_accrues_interest_and_is_repaid_from_deliveries() {
+        let mut enterprise = Enterprise::begin_enterprise();
+        enterprise.apply(Transaction::Loan { amount: 1000 });
+        assert!(enterprise.try_jump("Striking Out!", 0));
+        assert_eq!(enterprise.debt(), 1050);
+        enterprise.deliver(&AsteroidType::Big, 100.0, 2.0);
+        assert_eq!(enterprise.debt(), 1000);
+        assert_eq!(enterprise.funds(), 1150);
+        assert_eq!(enterprise.repay(), 1000);
+        assert_eq!(enterprise.debt(), 0);
+        assert_eq!(enterprise.funds(), 150);
+    }
+
+    #[test]
+    fn too_much_debt_is_bankruptcy() {
+        let mut enterprise = Enterprise::begin_enterprise();
+        while !enterprise.bankrupt() {
+            enterprise.burn_fuel(100.0);
+            enterprise.refuel(DEFAULT_FUEL_PRICE);
+        }
+        enterprise.declare_bankruptcy();
+        assert_eq!(enterprise.bankruptcies(), 1);
+        assert_eq!(enterprise.debt(), 0);
+        assert_eq!(enterprise.funds(), 0);
+        assert_eq!(enterprise.fuel(), 100.0);
+        assert_eq!(enterprise.ledger().len(), 1);
+    }
+
+    #[test]
+    fn jumping_needs_the_fee() {
+        let mut enterprise = Enterprise::begin_enterprise();
+        assert!(!enterprise.try_jump("Striking Out!", 100));
+        assert!(enterprise.tried_jump());
+        enterprise.deliver(&AsteroidType::Big, 100.0, 1.0);
+        assert!(enterprise.try_jump("Striking Out!", 100));
+        assert_eq!(enterprise.funds(), 0);
+        assert_eq!(enterprise.level_summary().jump_fee, 100);
+    }
+
+    #[test]
+    fn summaries_start_at_the_latest_arrival() {
+        let mut enterprise = Enterprise::begin_enterprise();
+        enterprise.arrive("First", &[]);
+        enterprise.deliver(&AsteroidType::Big, 100.0, 1.0);
+        enterprise.arrive("Second", &[]);
+        enterprise.deliver(&AsteroidType::Big, 10.0, 1.0);
+        assert_eq!(enterprise.level_summary().income, 10);
+    }
+
+    #[test]
+    fn arrivals_keep_only_the_previous_level() {
+        let mut enterprise = Enterprise::begin_enterprise();
+        for level in ["First", "Second", "Third"] {
+            enterprise.arrive(level, &[]);
+            enterprise.deliver(&AsteroidType::Big, 10.0, 1.0);
+        }
+        assert_eq!(enterprise.ledger().len(), 4);
+        assert_eq!(
+            enterprise.ledger()[0],
+            Transaction::Arrival {
+                level: "Second".to_string()
             }
-        })
+        );
     }
-}
 
-pub struct EconomyBundle;
-impl<'a, 'b> SystemBundle<'a, 'b> for EconomyBundle {
-    fn build(
-        self,
-        _world: &mut World,
-        dispatcher: &mut DispatcherBuilder<'a, 'b>,
-    ) -> Result<(), Error> {
-        dispatcher.add(MoneyHudSystem, "money_hud", &[]);
-        dispatcher.add(
-            InsufficientFundsWarningSystem,
-            "insufficient_funds_cooldown",
-            &[],
+    #[test]
+    fn deliveries_flood_the_market_until_it_recovers() {
+        let mut enterprise = Enterprise::begin_enterprise();
+        enterprise.deliver(&AsteroidType::Big, 20.0, 10.0);
+        assert_eq!(enterprise.funds(), 200);
+        assert_eq!(
+            enterprise.market().trend(&AsteroidType::Big),
+            Trend::Falling
         );
-        Ok(())
+        assert!((enterprise.market().price(&AsteroidType::Big, 10.0) - 9.0).abs() < 0.001);
+        assert_eq!(enterprise.market().price(&AsteroidType::Small, 10.0), 10.0);
+        enterprise.market_mut().recover(10.0);
+        assert_eq!(enterprise.market().trend(&AsteroidType::Big), Trend::Steady);
+    }
+
+    #[test]
+    fn market_news_fades() {
+        let mut market = Market::default();
+        let mut rng = crate::rng::GameRng::new(Some(0));
+        while market.news().is_none() {
+            market.shake(&mut rng, &[AsteroidType::Medium]);
+        }
+        let factor = market.news().unwrap().factor;
+        assert_eq!(market.price(&AsteroidType::Medium, 1.0), factor);
+        market.shake(&mut rng, &[]);
+        let faded = market.price(&AsteroidType::Medium, 1.0);
+        assert!((faded - 1.0).abs() < (factor - 1.0).abs());
+    }
+
+    #[test]
+    fn contracts_are_settled_on_jumping() {
+        let contracts = [
+            Contract {
+                goal: ContractGoal::Count {
+                    asteroid: AsteroidType::Artifact,
+                    count: 2,
+                },
+                reward: 500,
+                penalty: 0,
+            },
+            Contract {
+                goal: ContractGoal::Mass {
+                    asteroid: AsteroidType::Big,
+                    mass: 30.0,
+                },
+                reward: 100,
+                penalty: 50,
+            },
+            Contract {
+                goal: ContractGoal::Avoid {
+                    asteroid: AsteroidType::Bomb,
+                },
+                reward: 0,
+                penalty: 300,
+            },
+        ];
+        let mut enterprise = Enterprise::begin_enterprise();
+        enterprise.arrive("Contracted", &contracts);
+        enterprise.deliver(&AsteroidType::Artifact, 10.0, 10.0);
+        enterprise.deliver(&AsteroidType::Artifact, 10.0, 10.0);
+        enterprise.deliver(&AsteroidType::Bomb, 20.0, 0.0);
+        let met: Vec<bool> = enterprise.contracts().iter().map(|p| p.met()).collect();
+        assert_eq!(met, vec![true, false, false]);
+        let funds = enterprise.funds();
+        assert!(enterprise.try_jump("Contracted", 0));
+        let summary = enterprise.level_summary();
+        assert_eq!(summary.contract_rewards, 500);
+        assert_eq!(summary.contract_penalties, 350);
+        assert_eq!(enterprise.funds(), funds + 500 - 350);
+        assert!(enterprise.contracts().is_empty());
+    }
+
+    #[test]
+    fn penalties_beyond_the_funds_become_debt() {
+        let mut enterprise = Enterprise::begin_enterprise();
+        enterprise.apply(Transaction::ContractPenalty { amount: 300 });
+        assert_eq!(enterprise.funds(), 0);
+        assert_eq!(enterprise.debt(), 300);
+    }
+
+    #[test]
+    fn scores_reward_pace_and_keep_the_best() {
+        let summary = LevelSummary {
+            income: 1000,
+            contract_rewards: 200,
+            contract_penalties: 100,
+            contracts_met: 1,
+            ..LevelSummary::default()
+        };
+        assert_eq!(score(&summary, 50.0, 60.0, None), 1850);
+        assert_eq!(score(&summary, 50.0, 30.0, Some(60.0)), 3700);
+        assert_eq!(score(&summary, 50.0, 600.0, Some(60.0)), 925);
+        let mut enterprise = Enterprise::begin_enterprise();
+        assert!(enterprise.record_score("Level", 100));
+        assert!(!enterprise.record_score("Level", 50));
+        assert_eq!(enterprise.latest_score(), Some(50));
+        assert_eq!(enterprise.best_score("Level"), Some(100));
+        enterprise.arrive("Level", &[]);
+        assert_eq!(enterprise.latest_score(), None);
+    }
+
+    #[test]
+    fn levels_unlock_by_completions() {
+        let tutorial = Unlock {
+            after: 0,
+            until: Some(1),
+            rest: 0,
+        };
+        let later = Unlock {
+            after: 1,
+            until: None,
+            rest: 1,
+        };
+        let mut enterprise = Enterprise::begin_enterprise();
+        assert!(enterprise.offers("Tutorial", &tutorial));
+        assert!(!enterprise.offers("Later", &later));
+        enterprise.try_jump("Tutorial", 0);
+        assert!(!enterprise.offers("Tutorial", &tutorial));
+        assert!(enterprise.offers("Later", &later));
+        enterprise.try_jump("Later", 0);
+        assert!(!enterprise.offers("Later", &later));
     }
 }
@@ -0,0 +1,816 @@
+//! The level editor, offered on the main menu in dev mode. It draws a level's boundaries, fields
+//! and delivery zones, lets them be dragged around and writes the level back to its RON file.
+//!
+//! Left drag moves whatever is under the mouse and the arrow keys pan. F3 places a billboard and
+//! F4 a delivery zone at the mouse, Delete removes what's selected and Page Up/Down pick the
+//! billboard sprite. Home/End pick an asteroid whose price the numpad +/- changes and F6 resets.
+//! F2 saves, F7/F8 switch levels and Escape goes back.
+
+use std::{collections::HashSet, fs, path::PathBuf};
+
+use amethyst::{
+    assets::AssetStorage,
+    core::{
+        math::{Point2, Point3, Vector3},
+        SystemBundle, Transform,
+    },
+    ecs::*,
+    input::{is_key_down, InputHandler, StringBindings, VirtualKeyCode},
+    prelude::*,
+    renderer::{debug_drawing::DebugLines, palette::Srgba, sprite::SpriteSheet, Camera},
+    ui::{UiCreator, UiText, UiTransform},
+    utils::application_root_dir,
+    window::ScreenDimensions,
+    winit::MouseButton,
+    Error,
+};
+
+use crate::{
+    assets::SpriteStorage,
+    asteroid::AsteroidType,
+    billboards::{generate_billboard, BillboardDesc},
+    level::{AsteroidDesc, Level},
+    materials::Materials,
+    menu::find_by_id,
+    placement::Area,
+    ASSETS,
+};
+
+/// How close, in world units, the mouse has to be to pick something up.
+const REACH: f32 = 20.0;
+/// How far the arrow keys pan each frame.
+const PAN: f32 = 10.0;
+const PRICE_STEP: f32 = 0.5;
+
+/// Something in the level that can be dragged.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Grip {
+    /// The top right corner of the boundaries, which resizes them.
+    Corner,
+    PlayerStart,
+    Delivery(usize),
+    Field(usize),
+    /// The top right corner of a field, which resizes it.
+    FieldCorner(usize),
+    Billboard(usize),
+}
+
+/// A level being edited and what the editor has selected in it.
+pub struct LevelEditor {
+    pub path: String,
+    pub level: Level,
+    pub selected: Option<Grip>,
+    /// The sprite for the next billboard placed.
+    pub sprite_number: usize,
+    /// The asteroid whose price is being changed.
+    pub priced: Option<AsteroidType>,
+    pub status: String,
+    /// Set when billboards are added, removed or change sprite, so they're drawn again.
+    billboards_changed: bool,
+    /// Set once the UI shows this level's reference.
+    ui_filled: bool,
+}
+
+impl LevelEditor {
+    pub fn new(path: String, level: Level) -> Self {
+        LevelEditor {
+            path,
+            level,
+            selected: None,
+            sprite_number: 0,
+            priced: None,
+            status: String::new(),
+            billboards_changed: true,
+            ui_filled: false,
+        }
+    }
+
+    /// A field's rectangle as centre and size, with fields that don't say filling the level.
+    fn field_rect(&self, index: usize) -> Option<(f32, f32, f32, f32)> {
+        let (width, height) = self.level.boundaries;
+        match self.level.asteroids.get(index)? {
+            AsteroidDesc::Field {
+                location,
+                area: None,
+                ..
+            } => Some(location.unwrap_or((0.0, 0.0, width, height))),
+            _ => None,
+        }
+    }
+
+    /// Where a placement other than a field sits.
+    fn placement_center(&self, index: usize) -> Option<(f32, f32)> {
+        match self.level.asteroids.get(index)? {
+            AsteroidDesc::Field { area, .. } => area.as_ref().map(Area::center),
+            AsteroidDesc::Single { position, .. } => Some(*position),
+            AsteroidDesc::Ring { center, .. } | AsteroidDesc::Cluster { center, .. } => {
+                Some(*center)
+            }
+            AsteroidDesc::Stream { origin, .. } => Some(*origin),
+        }
+    }
+
+    fn set_placement_center(&mut self, index: usize, point: (f32, f32)) {
+        match self.level.asteroids.get_mut(index) {
+            Some(AsteroidDesc::Field {
+                area: Some(area), ..
+            }) => area.move_to(point),
+            Some(AsteroidDesc::Single { position, .. }) => *position = point,
+            Some(AsteroidDesc::Ring { center, .. })
+            | Some(AsteroidDesc::Cluster { center, .. }) => *center = point,
+            Some(AsteroidDesc::Stream { origin, .. }) => *origin = point,
+            _ => {}
+        }
+    }
+
+    fn set_field_rect(&mut self, index: usize, rect: (f32, f32, f32, f32)) {
+        if let Some(AsteroidDesc::Field { location, .. }) = self.level.asteroids.get_mut(index) {
+            *location = Some(rect);
+        }
+    }
+
+    pub fn grips(&self) -> Vec<(Grip, (f32, f32))> {
+        let mut grips = vec![(Grip::Corner, self.level.boundaries)];
+        if let Some(player_start) = self.level.player_start {
+            grips.push((Grip::PlayerStart, player_start));
+        }
+        for (index, delivery) in self.level.deliveries.iter().enumerate() {
+            grips.push((Grip::Delivery(index), *delivery));
+        }
+        for index in 0..self.level.asteroids.len() {
+            if let Some((x, y, width, height)) = self.field_rect(index) {
+                grips.push((Grip::Field(index), (x, y)));
+                grips.push((
+                    Grip::FieldCorner(index),
+                    (x + width / 2.0, y + height / 2.0),
+                ));
+            } else if let Some(center) = self.placement_center(index) {
+                grips.push((Grip::Field(index), center));
+            }
+        }
+        for (index, billboard) in self.level.billboards.iter().enumerate() {
+            grips.push((Grip::Billboard(index), billboard.location));
+        }
+        grips
+    }
+
+    /// The closest grip within `reach` of a point.
+    pub fn grip_at(&self, (x, y): (f32, f32), reach: f32) -> Option<Grip> {
+        self.grips()
+            .into_iter()
+            .map(|(grip, (gx, gy))| (grip, (gx - x).hypot(gy - y)))
+            .filter(|(_, distance)| *distance <= reach)
+            .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+            .map(|(grip, _)| grip)
+    }
+
+    pub fn move_grip(&mut self, grip: Grip, (x, y): (f32, f32)) {
+        match grip {
+            Grip::Corner => self.level.boundaries = (x.abs().max(REACH), y.abs().max(REACH)),
+            Grip::PlayerStart => self.level.player_start = Some((x, y)),
+            Grip::Delivery(index) => {
+                if let Some(delivery) = self.level.deliveries.get_mut(index) {
+                    *delivery = (x, y);
+                }
+            }
+            Grip::Field(index) => {
+                if let Some((_, _, width, height)) = self.field_rect(index) {
+                    self.set_field_rect(index, (x, y, width, height));
+                } else {
+                    self.set_placement_center(index, (x, y));
+                }
+            }
+            Grip::FieldCorner(index) => {
+                if let Some((cx, cy, _, _)) = self.field_rect(index) {
+                    let width = ((x - cx) * 2.0).max(REACH);
+                    let height = ((y - cy) * 2.0).max(REACH);
+                    self.set_field_rect(index, (cx, cy, width, height));
+                }
+            }
+            Grip::Billboard(index) => {
+                if let Some(billboard) = self.level.billboards.get_mut(index) {
+                    billboard.location = (x, y);
+                }
+            }
+        }
+    }
+
+    pub fn place_billboard(&mut self, location: (f32, f32)) {
+        self.level.billboards.push(BillboardDesc {
+            location,
+            sprite_number: self.sprite_number,
+        });
+        self.selected = Some(Grip::Billboard(self.level.billboards.len() - 1));
+        self.billboards_changed = true;
+    }
+
+    pub fn add_delivery(&mut self, location: (f32, f32)) {
+        self.level.deliveries.push(location);
+        self.selected = Some(Grip::Delivery(self.level.deliveries.len() - 1));
+    }
+
+    /// Removes the selected delivery zone, field or billboard. The boundaries and player start
+    /// always stay.
+    pub fn remove_selected(&mut self) {
+        match self.selected.take() {
+            Some(Grip::Delivery(index)) if index < self.level.deliveries.len() => {
+                self.level.deliveries.remove(index);
+            }
+            Some(Grip::Field(index)) | Some(Grip::FieldCorner(index))
+                if index < self.level.asteroids.len() =>
+            {
+                self.level.asteroids.remove(index);
+            }
+            Some(Grip::Billboard(index)) if index < self.level.billboards.len() => {
+                self.level.billboards.remove(index);
+                self.billboards_changed = true;
+            }
+            selected => self.selected = selected,
+        }
+    }
+
+    /// Steps through the sprite sheet, for the selected billboard if there is one.
+    pub fn change_sprite(&mut self, step: isize, sprite_count: usize) {
+        let sprite_count = sprite_count.max(1) as isize;
+        let next = |sprite_number: usize| {
+            (sprite_number as isize + step).rem_euclid(sprite_count) as usize
+        };
+        self.sprite_number = next(self.sprite_number);
+        if let Some(Grip::Billboard(index)) = self.selected {
+            if let Some(billboard) = self.level.billboards.get_mut(index) {
+                billboard.sprite_number = next(billboard.sprite_number);
+                self.sprite_number = billboard.sprite_number;
+                self.billboards_changed = true;
+            }
+        }
+    }
+
+    /// Steps through the catalogue for the asteroid whose price is being changed.
+    pub fn change_priced(&mut self, step: isize, materials: &Materials) {
+        let types = materials.types();
+        if types.is_empty() {
+            return;
+        }
+        let current = self
+            .priced
+            .as_ref()
+            .and_then(|priced| types.iter().position(|asteroid| asteroid == priced));
+        let index = match current {
+            Some(index) => (index as isize + step).rem_euclid(types.len() as isize) as usize,
+            None => 0,
+        };
+        self.priced = Some(types[index].clone());
+    }
+
+    pub fn adjust_price(&mut self, delta: f32, materials: &Materials) {
+        if let Some(priced) = self.priced.clone() {
+            let price = self.level.get_ppm(materials, &priced);
+            self.level.set_price(priced, (price + delta).max(0.0));
+        }
+    }
+
+    pub fn reset_price(&mut self) {
+        if let Some(priced) = &self.priced {
+            self.level.reset_price(priced);
+        }
+    }
+
+    /// The level as RON, as long as it would play as written.
+    pub fn to_ron(
+        &self,
+        sprite_count: usize,
+        materials: &Materials,
+    ) -> Result<String, Vec<String>> {
+        let problems = self.level.problems(sprite_count, materials);
+        if !problems.is_empty() {
+            return Err(problems);
+        }
+        ron::ser::to_string_pretty(&self.level, ron::ser::PrettyConfig::default())
+            .map_err(|error| vec![error.to_string()])
+    }
+
+    /// Writes the level over its file in the assets directory.
+    pub fn save(&self, sprite_count: usize, materials: &Materials) -> Result<PathBuf, Vec<String>> {
+        let ron = self.to_ron(sprite_count, materials)?;
+        let path = application_root_dir()
+            .map_err(|error| vec![error.to_string()])?
+            .join("assets")
+            .join(&self.path);
+        fs::write(&path, ron).map_err(|error| vec![error.to_string()])?;
+        Ok(path)
+    }
+
+    fn describe(&self, materials: &Materials) -> String {
+        let priced = match &self.priced {
+            Some(priced) => format!(
+                "{:?} sells for {:.1}",
+                priced,
+                self.level.get_ppm(materials, priced)
+            ),
+            None => "Home/End to pick a price".to_string(),
+        };
+        format!(
+            "{} - selected: {:?} - sprite {} - {}\n{}",
+            self.path, self.selected, self.sprite_number, priced, self.status
+        )
+    }
+}
+
+/// Shows one of the level's billboards in the editor.
+#[derive(Component)]
+#[storage(VecStorage)]
+pub struct EditorBillboard(usize);
+
+/// Picks up input and draws the level, whenever there's a level being edited.
+#[derive(Default)]
+pub struct LevelEditorSystem {
+    /// Keys that were down last frame, so holding one only acts once.
+    held: HashSet<VirtualKeyCode>,
+    dragging: bool,
+}
+
+impl LevelEditorSystem {
+    /// Whether a key went down since the last frame.
+    fn pressed(&self, input: &InputHandler<StringBindings>, key: VirtualKeyCode) -> bool {
+        input.key_is_down(key) && !self.held.contains(&key)
+    }
+}
+
+impl<'s> System<'s> for LevelEditorSystem {
+    type SystemData = (
+        Option<Write<'s, LevelEditor>>,
+        Option<Read<'s, InputHandler<StringBindings>>>,
+        Option<Read<'s, ScreenDimensions>>,
+        Option<Write<'s, DebugLines>>,
+        ReadStorage<'s, Camera>,
+        WriteStorage<'s, Transform>,
+        WriteStorage<'s, EditorBillboard>,
+        WriteStorage<'s, UiTransform>,
+        WriteStorage<'s, UiText>,
+        Read<'s, Materials>,
+        Option<Read<'s, SpriteStorage>>,
+        Read<'s, AssetStorage<SpriteSheet>>,
+        Read<'s, LazyUpdate>,
+        Entities<'s>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            editor,
+            input,
+            dimensions,
+            lines,
+            cameras,
+            mut transforms,
+            mut editor_billboards,
+            ui_transforms,
+            mut texts,
+            materials,
+            sprites,
+            sprite_sheets,
+            update,
+            entities,
+        ): Self::SystemData,
+    ) {
+        let (mut editor, input) = match (editor, input) {
+            (Some(editor), Some(input)) => (editor, input),
+            _ => return,
+        };
+        let sprite_count = sprites
+            .as_ref()
+            .and_then(|sprites| sprite_sheets.get(&sprites.sprites))
+            .map(|sheet| sheet.sprites.len());
+
+        let camera = (&cameras, &entities)
+            .join()
+            .next()
+            .map(|(_, entity)| entity);
+        let mouse = match (camera, &dimensions, input.mouse_position()) {
+            (Some(camera), Some(dimensions), Some((x, y))) => {
+                match (cameras.get(camera), transforms.get(camera)) {
+                    (Some(lens), Some(transform)) => {
+                        let point = lens.screen_to_world_point(
+                            Point3::new(x, y, 0.0),
+                            dimensions.diagonal(),
+                            transform,
+                        );
+                        Some((point.x, point.y))
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        if let Some(transform) = camera.and_then(|camera| transforms.get_mut(camera)) {
+            for (key, (dx, dy)) in [
+                (VirtualKeyCode::Left, (-PAN, 0.0)),
+                (VirtualKeyCode::Right, (PAN, 0.0)),
+                (VirtualKeyCode::Up, (0.0, PAN)),
+                (VirtualKeyCode::Down, (0.0, -PAN)),
+            ] {
+                if input.key_is_down(key) {
+                    transform.prepend_translation_x(dx);
+                    transform.prepend_translation_y(dy);
+                }
+            }
+        }
+
+        if let Some(mouse) = mouse {
+            let clicked = input.mouse_button_is_down(MouseButton::Left);
+            if clicked && !self.dragging {
+                editor.selected = editor.grip_at(mouse, REACH);
+            }
+            if clicked {
+                if let Some(grip) = editor.selected {
+                    editor.move_grip(grip, mouse);
+                }
+            }
+            self.dragging = clicked;
+            if self.pressed(&input, VirtualKeyCode::F3) {
+                editor.place_billboard(mouse);
+            }
+            if self.pressed(&input, VirtualKeyCode::F4) {
+                editor.add_delivery(mouse);
+            }
+        }
+        if self.pressed(&input, VirtualKeyCode::Delete) {
+            editor.remove_selected();
+        }
+        if let (true, Some(sprite_count)) =
+            (self.pressed(&input, VirtualKeyCode::PageUp), sprite_count)
+        {
+            editor.change_sprite(1, sprite_count);
+        }
+        if let (true, Some(sprite_count)) =
+            (self.pressed(&input, VirtualKeyCode::PageDown), sprite_count)
+        {
+            editor.change_sprite(-1, sprite_count);
+        }
+        if self.pressed(&input, VirtualKeyCode::Home) {
+            editor.change_priced(-1, &materials);
+        }
+        if self.pressed(&input, VirtualKeyCode::End) {
+            editor.change_priced(1, &materials);
+        }
+        if self.pressed(&input, VirtualKeyCode::Add) {
+            editor.adjust_price(PRICE_STEP, &materials);
+        }
+        if self.pressed(&input, VirtualKeyCode::Subtract) {
+            editor.adjust_price(-PRICE_STEP, &materials);
+        }
+        if self.pressed(&input, VirtualKeyCode::F6) {
+            editor.reset_price();
+        }
+
+        // The reference is typed straight into the UI and read back from it.
+        let ui_filled = editor.ui_filled;
+        let reference = &mut editor.level.reference;
+        for (id, value) in [
+            ("editor_name", &mut reference.name),
+            ("editor_description", &mut reference.description),
+        ] {
+            if let Some(text) =
+                find_by_id(&entities, &ui_transforms, id).and_then(|field| texts.get_mut(field))
+            {
+                if ui_filled {
+                    *value = text.text.clone();
+                } else {
+                    text.text = value.clone();
+                }
+            }
+        }
+        editor.ui_filled = find_by_id(&entities, &ui_transforms, "editor_name").is_some();
+
+        if self.pressed(&input, VirtualKeyCode::F2) {
+            editor.status = match editor.save(sprite_count.unwrap_or(usize::MAX), &materials) {
+                Ok(path) => format!("Saved to {}", path.display()),
+                Err(problems) => format!("Not saved: {}", problems.join("; ")),
+            };
+        }
+        if let Some(text) = find_by_id(&entities, &ui_transforms, "editor_status")
+            .and_then(|status| texts.get_mut(status))
+        {
+            text.text = editor.describe(&materials);
+        }
+
+        if editor.billboards_changed {
+            editor.billboards_changed = false;
+            for (entity, _) in (&entities, &editor_billboards).join() {
+                entities.delete(entity).ok();
+            }
+            if let Some(sprites) = &sprites {
+                for (index, billboard) in editor.level.billboards.iter().enumerate() {
+                    let builder = update.create_entity(&entities).with(EditorBillboard(index));
+                    generate_billboard(builder, sprites.sprites.clone(), billboard);
+                }
+            }
+        } else {
+            for (billboard, transform) in (&mut editor_billboards, &mut transforms).join() {
+                if let Some(desc) = editor.level.billboards.get(billboard.0) {
+                    transform.set_translation_x(desc.location.0);
+                    transform.set_translation_y(desc.location.1);
+                }
+            }
+        }
+
+        if let Some(mut lines) = lines {
+            draw_level(&mut lines, &editor);
+        }
+
+        self.held.clear();
+        for key in [
+            VirtualKeyCode::F2,
+            VirtualKeyCode::F3,
+            VirtualKeyCode::F4,
+            VirtualKeyCode::F6,
+            VirtualKeyCode::Delete,
+            VirtualKeyCode::PageUp,
+            VirtualKeyCode::PageDown,
+            VirtualKeyCode::Home,
+            VirtualKeyCode::End,
+            VirtualKeyCode::Add,
+            VirtualKeyCode::Subtract,
+        ] {
+            if input.key_is_down(key) {
+                self.held.insert(key);
+            }
+        }
+    }
+}
+
+fn draw_level(lines: &mut DebugLines, editor: &LevelEditor) {
+    let white = Srgba::new(1.0, 1.0, 1.0, 1.0);
+    let green = Srgba::new(0.0, 1.0, 0.4, 1.0);
+    let yellow = Srgba::new(0.92, 1.0, 0.0, 1.0);
+    let red = Srgba::new(1.0, 0.2, 0.2, 1.0);
+    let (width, height) = editor.level.boundaries;
+    lines.draw_rectangle(
+        Point2::new(-width, -height),
+        Point2::new(width, height),
+        0.0,
+        white,
+    );
+    for (index, asteroid_desc) in editor.level.asteroids.iter().enumerate() {
+        match asteroid_desc {
+            AsteroidDesc::Field { .. } => match asteroid_desc.field_area(editor.level.boundaries) {
+                Some(Area::Circle { center, radius }) => {
+                    lines.draw_circle(Point3::new(center.0, center.1, 0.0), radius, 32, yellow);
+                }
+                Some(Area::Polygon(vertices)) => {
+                    for ((x1, y1), (x2, y2)) in vertices.iter().zip(vertices.iter().cycle().skip(1))
+                    {
+                        lines.draw_line(
+                            Point3::new(*x1, *y1, 0.0),
+                            Point3::new(*x2, *y2, 0.0),
+                            yellow,
+                        );
+                    }
+                }
+                Some(area) => {
+                    let ((min_x, min_y), (max_x, max_y)) = area.bounds();
+                    lines.draw_rectangle(
+                        Point2::new(min_x, min_y),
+                        Point2::new(max_x, max_y),
+                        0.0,
+                        yellow,
+                    );
+                }
+                None => {}
+            },
+            AsteroidDesc::Single { position, .. } => {
+                lines.draw_circle(Point3::new(position.0, position.1, 0.0), 8.0, 8, yellow);
+            }
+            AsteroidDesc::Ring { center, radius, .. } => {
+                lines.draw_circle(Point3::new(center.0, center.1, 0.0), *radius, 32, yellow);
+            }
+            AsteroidDesc::Cluster { center, spread, .. } => {
+                lines.draw_circle(Point3::new(center.0, center.1, 0.0), *spread, 32, yellow);
+                lines.draw_circle(
+                    Point3::new(center.0, center.1, 0.0),
+                    spread * 2.0,
+                    32,
+                    yellow,
+                );
+            }
+            AsteroidDesc::Stream {
+                origin, velocity, ..
+            } => {
+                lines.draw_direction(
+                    Point3::new(origin.0, origin.1, 0.0),
+                    Vector3::new(velocity.0, velocity.1, 0.0),
+                    yellow,
+                );
+            }
+        }
+    }
+    for (x, y) in editor.level.deliveries.iter() {
+        lines.draw_circle(Point3::new(*x, *y, 0.0), 75.0, 32, green);
+    }
+    for (grip, (x, y)) in editor.grips() {
+        let color = if editor.selected == Some(grip) {
+            red
+        } else {
+            white
+        };
+        lines.draw_circle(Point3::new(x, y, 0.0), REACH / 2.0, 12, color);
+    }
+}
+
+pub struct EditorBundle;
+impl<'a, 'b> SystemBundle<'a, 'b> for EditorBundle {
+    fn build(
+        self,
+        _world: &mut World,
+        dispatcher: &mut DispatcherBuilder<'a, 'b>,
+    ) -> Result<(), Error> {
+        dispatcher.add(LevelEditorSystem::default(), "level_editor", &[]);
+        Ok(())
+    }
+}
+
+/// Edits the levels listed in `levels.ron`, one at a time.
+pub struct EditorState {
+    assets: ASSETS,
+    index: usize,
+}
+
+impl EditorState {
+    pub fn new(assets: ASSETS) -> Self {
+        EditorState { assets, index: 0 }
+    }
+
+    fn switch_to(&self, step: isize) -> SimpleTrans {
+        let count = self.assets.1.levels.len().max(1) as isize;
+        Trans::Switch(Box::new(EditorState {
+            assets: self.assets.clone(),
+            index: (self.index as isize + step).rem_euclid(count) as usize,
+        }))
+    }
+}
+
+impl SimpleState for EditorState {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        data.world.delete_all();
+        data.world.insert(self.assets.0.clone());
+        let materials = {
+            let handle = &self.assets.4.materials;
+            data.world
+                .read_resource::<AssetStorage<Materials>>()
+                .get(handle)
+                .cloned()
+        };
+        if let Some(materials) = materials {
+            data.world.insert(materials);
+        }
+        let level = self.assets.1.levels.get(self.index).and_then(|handle| {
+            data.world
+                .read_resource::<AssetStorage<Level>>()
+                .get(handle)
+                .cloned()
+        });
+        if let (Some(level), Some(path)) = (level, self.assets.1.paths.get(self.index)) {
+            data.world.insert(LevelEditor::new(path.clone(), level));
+        }
+
+        let (width, height) = {
+            let dimensions = data.world.read_resource::<ScreenDimensions>();
+            (dimensions.width(), dimensions.height())
+        };
+        let mut transform = Transform::default();
+        transform.set_translation_xyz(0.0, 0.0, 100.0);
+        data.world
+            .create_entity()
+            .with(Camera::standard_2d(width, height))
+            .with(transform)
+            .build();
+        data.world.exec(|mut creator: UiCreator<'_>| {
+            creator.create("ui/editor.ron", ());
+        });
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        data.world.remove::<LevelEditor>();
+        data.world.delete_all();
+    }
+
+    fn handle_event(
+        &mut self,
+        _data: StateData<'_, GameData<'_, '_>>,
+        event: StateEvent,
+    ) -> SimpleTrans {
+        if let StateEvent::Window(event) = &event {
+            if is_key_down(event, VirtualKeyCode::Escape) {
+                return Trans::Pop;
+            }
+            if is_key_down(event, VirtualKeyCode::F7) {
+                return self.switch_to(-1);
+            }
+            if is_key_down(event, VirtualKeyCode::F8) {
+                return self.switch_to(1);
+            }
+        }
+        Trans::None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{level_from_ron, load_asset};
+
+    fn editor() -> LevelEditor {
+        let level = level_from_ron(
+            r#"#![enable(implicit_some)]
+            Level(
+                boundaries: (500.0, 500.0),
+                player_start: (0.0, -400.0),
+                deliveries: [(0.0, 400.0)],
+                jump_cost: 100,
+                asteroids: [Field(normal: 10, location: (100.0, 0.0, 200.0, 200.0))],
+                billboards: [],
+                card: (title: "Test", sprite_number: 0),
+                reference: (name: "Test", description: "", shown_prices: []),
+            )"#,
+        );
+        LevelEditor::new("levels/test.ron".to_string(), level)
+    }
+
+    #[test]
+    fn grips_are_picked_up_and_moved() {
+        let mut editor = editor();
+        assert_eq!(editor.grip_at((5.0, 395.0), REACH), Some(Grip::Delivery(0)));
+        assert_eq!(editor.grip_at((0.0, 0.0), REACH), None);
+        editor.move_grip(Grip::Field(0), (-100.0, 50.0));
+        assert_eq!(
+            editor.grip_at((0.0, 150.0), REACH),
+            Some(Grip::FieldCorner(0))
+        );
+        editor.move_grip(Grip::FieldCorner(0), (0.0, 250.0));
+        assert_eq!(editor.field_rect(0), Some((-100.0, 50.0, 200.0, 400.0)));
+    }
+
+    #[test]
+    fn placements_are_moved_by_their_centre() {
+        let mut editor = editor();
+        let ring: AsteroidDesc = ron::de::from_str(
+            "Ring(center: (-300.0, 0.0), radius: 80.0, count: 6, types: [Small, Bomb])",
+        )
+        .unwrap();
+        editor.level.asteroids.push(ring);
+        assert_eq!(editor.grip_at((-300.0, 5.0), REACH), Some(Grip::Field(1)));
+        editor.move_grip(Grip::Field(1), (-250.0, 100.0));
+        assert_eq!(editor.placement_center(1), Some((-250.0, 100.0)));
+        assert_eq!(editor.field_rect(1), None);
+    }
+
+    #[test]
+    fn billboards_and_deliveries_are_placed_and_removed() {
+        let mut editor = editor();
+        editor.change_sprite(-1, 77);
+        editor.place_billboard((10.0, 10.0));
+        assert_eq!(editor.level.billboards[0].sprite_number, 76);
+        editor.change_sprite(1, 77);
+        assert_eq!(editor.level.billboards[0].sprite_number, 0);
+        editor.remove_selected();
+        assert!(editor.level.billboards.is_empty());
+        editor.add_delivery((-200.0, 0.0));
+        assert_eq!(editor.level.deliveries.len(), 2);
+        editor.selected = Some(Grip::Corner);
+        editor.remove_selected();
+        assert_eq!(editor.selected, Some(Grip::Corner));
+    }
+
+    #[test]
+    fn saved_levels_load_back_with_their_changes() {
+        let materials = load_asset::<Materials>("materials.ron");
+        let mut editor = editor();
+        editor.change_priced(1, &materials);
+        let priced = editor.priced.clone().unwrap();
+        let price = editor.level.get_ppm(&materials, &priced);
+        editor.adjust_price(PRICE_STEP, &materials);
+        editor.move_grip(Grip::Delivery(0), (50.0, 300.0));
+        editor.level.reference.name = "Renamed".to_string();
+
+        let ron = editor.to_ron(77, &materials).unwrap();
+        let level = level_from_ron(&ron);
+        assert_eq!(level.get_ppm(&materials, &priced), price + PRICE_STEP);
+        assert_eq!(level.deliveries, vec![(50.0, 300.0)]);
+        assert_eq!(level.reference.name, "Renamed");
+
+        editor.move_grip(Grip::Delivery(0), (50.0, 3000.0));
+        assert!(editor.to_ron(77, &materials).is_err());
+    }
+
+    #[test]
+    fn crowded_fields_are_flagged() {
+        let materials = load_asset::<Materials>("materials.ron");
+        let mut editor = editor();
+        assert!(editor.to_ron(77, &materials).is_ok());
+        editor.level.asteroids[0] = ron::de::from_str(
+            "#![enable(implicit_some)] Field(normal: 400, location: (100.0, 0.0, 200.0, 200.0))",
+        )
+        .unwrap();
+        assert!(editor.to_ron(77, &materials).is_err());
+    }
+}
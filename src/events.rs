@@ -0,0 +1,37 @@
+//! What happens in a level, for whatever wants to know: each event is written to its own
+//! `EventChannel` by the system that made it happen.
+
+use crate::asteroid::AsteroidType;
+
+/// An asteroid sold in a delivery zone.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AsteroidDelivered {
+    pub asteroid: AsteroidType,
+    pub mass: f32,
+    pub income: u64,
+}
+
+/// Two asteroids that touched and reacted. A product of `None` means that reactant was used up.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AsteroidReacted {
+    pub reactants: (AsteroidType, AsteroidType),
+    pub products: (Option<AsteroidType>, Option<AsteroidType>),
+}
+
+/// An explosion going off, whether from a bomb, a reaction or anything else.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExplosionTriggered {
+    pub position: (f32, f32),
+    pub strength: f32,
+}
+
+/// The player paid the jump fee and the jump out of the level has begun.
+#[derive(Clone, Debug, PartialEq)]
+pub struct JumpStarted {
+    pub level: String,
+    pub jump_cost: u64,
+}
+
+/// The tank ran dry. Written once, when it happens, rather than on every frame it stays empty.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FuelDepleted;
@@ -4,16 +4,20 @@ use amethyst::{
     prelude::*,
     renderer::{sprite::SpriteSheetHandle, SpriteRender},
     shred::System,
+    shrev::EventChannel,
 };
 use nalgebra::Point2;
+use rand::Rng;
 
 use crate::{
     assets::{SpriteHandles, SpriteRes},
     asteroid::{resize_asteroid, Asteroid, AsteroidType},
     economy::Enterprise,
+    events::ExplosionTriggered,
     particles::{emit_particle, random_direction, Particle},
     physics::{Physics, PhysicsHandle},
     player::Player,
+    rng::GameRng,
 };
 
 #[derive(Component, Debug)]
@@ -66,6 +70,8 @@ impl<'s> System<'s> for ExplosionForceSystem {
         Write<'s, Enterprise>,
         Write<'s, Physics>,
         SpriteRes<'s>,
+        Write<'s, GameRng>,
+        Write<'s, EventChannel<ExplosionTriggered>>,
     );
 
     fn run(
@@ -81,6 +87,8 @@ impl<'s> System<'s> for ExplosionForceSystem {
             mut enterprise,
             mut physics,
             sprites,
+            mut rng,
+            mut triggered,
         ): Self::SystemData,
     ) {
         for (explosion, transform) in (&mut explosions, &transforms).join() {
@@ -90,13 +98,17 @@ impl<'s> System<'s> for ExplosionForceSystem {
             } = explosion
             {
                 let location = transform.translation();
-                let particle_count = ((rand::random::<f32>() * 20.0) as usize + 10);
+                triggered.single_write(ExplosionTriggered {
+                    position: (location.x, location.y),
+                    strength: *strength,
+                });
+                let particle_count = ((rng.gen::<f32>() * 20.0) as usize + 10);
                 for _ in 0..particle_count {
-                    let direction = random_direction();
+                    let direction = random_direction(&mut *rng);
                     emit_particle(
                         update.create_entity(&entities),
                         sprites.get_handle(),
-                        Particle::explosion(&particles, direction),
+                        Particle::explosion(&particles, direction, &mut *rng),
                         Point2::new(location.x, location.y),
                     );
                 }
@@ -143,11 +155,11 @@ impl<'s> System<'s> for ExplosionForceSystem {
                                 AsteroidType::EncasedArtifact => {
                                     asteroid.my_type = AsteroidType::Artifact;
                                     for _ in 0..10 {
-                                        let direction = random_direction();
+                                        let direction = random_direction(&mut *rng);
                                         emit_particle(
                                             update.create_entity(&entities),
                                             sprites.get_handle(),
-                                            Particle::explosion(&vec![33], direction),
+                                            Particle::explosion(&vec![33], direction, &mut *rng),
                                             Point2::new(asteroid_location.x, asteroid_location.y),
                                         );
                                     }
@@ -185,3 +197,42 @@ impl<'s> System<'s> for ExplosionForceSystem {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestWorldBuilder;
+
+    #[test]
+    fn explosions_break_nearby_asteroids() {
+        let mut test = TestWorldBuilder::new()
+            .with(ExplosionForceSystem, "explosion_force")
+            .build();
+        test.spawn_asteroid(AsteroidType::Big, (10.0, 0.0));
+        test.spawn_asteroid(AsteroidType::EncasedArtifact, (0.0, 10.0));
+        test.spawn_asteroid(AsteroidType::Big, (200.0, 0.0));
+        test.run(1);
+        test.spawn_explosion((0.0, 0.0), 500000.0);
+        test.run(1);
+        test.assert_asteroids(&[
+            AsteroidType::Artifact,
+            AsteroidType::Big,
+            AsteroidType::Medium,
+        ]);
+    }
+
+    #[test]
+    fn explosions_burn_fuel_close_by() {
+        let mut enterprise = Enterprise::begin_enterprise();
+        enterprise.burn_fuel(50.0);
+        let mut test = TestWorldBuilder::new()
+            .with(ExplosionForceSystem, "explosion_force")
+            .with_enterprise(enterprise)
+            .build();
+        test.spawn_player((30.0, 0.0));
+        test.run(1);
+        test.spawn_explosion((0.0, 0.0), 500000.0);
+        test.run(1);
+        assert!(test.fuel() < 50.0);
+    }
+}
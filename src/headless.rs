@@ -0,0 +1,171 @@
+use std::{fs::File, ops::Deref};
+
+use amethyst::{
+    assets::{AssetStorage, ProgressCounter},
+    core::{frame_limiter::FrameRateLimitStrategy, Time},
+    ecs::*,
+    prelude::*,
+    renderer::{Camera, SpriteRender, SpriteSheet, Texture},
+    utils::application_root_dir,
+    window::ScreenDimensions,
+    Application, GameData, GameDataBuilder, SimpleState, SimpleTrans, StateData, Trans,
+};
+
+use crate::{
+    assets::{
+        load_chemistry, load_level, load_materials, load_spritesheet, ChemistryStorage,
+        MaterialStorage, SpriteStorage,
+    },
+    billboards::Billboard,
+    delivery::DeliveryZone,
+    economy::Enterprise,
+    level::{initialize_level, Level, LevelHandle, LevelTimer},
+    replay::{begin_replay, finish_replay, next_input, prepare_replay, ReplayMode},
+    rng::{GameRng, StartupSeed},
+    with_gameplay_systems,
+};
+
+/// Every headless frame advances the game clock by the same amount, regardless of how fast the
+/// frame actually ran.
+const HEADLESS_FRAME: f32 = 1.0 / 60.0;
+
+pub struct HeadlessState {
+    level_path: String,
+    frames: usize,
+    seed: Option<u64>,
+    replay: ReplayMode,
+    elapsed: usize,
+    progress: Option<ProgressCounter>,
+    level: Option<LevelHandle>,
+    started: bool,
+}
+
+impl HeadlessState {
+    pub fn new(level_path: String, frames: usize, seed: Option<u64>) -> Self {
+        HeadlessState {
+            level_path,
+            frames,
+            seed,
+            replay: ReplayMode::Live,
+            elapsed: 0,
+            progress: None,
+            level: None,
+            started: false,
+        }
+    }
+
+    pub fn with_replay(mut self, replay: ReplayMode) -> Self {
+        if let ReplayMode::Playback(replay) = &replay {
+            self.frames = replay.frames.len();
+        }
+        self.replay = replay;
+        self
+    }
+
+    fn report(&self, world: &World) {
+        let enterprise = world.read_resource::<Enterprise>();
+        println!(
+            "{} (seed {}): {} frames, funds {}, fuel {:.2}",
+            self.level_path,
+            world.read_resource::<GameRng>().seed(),
+            self.elapsed,
+            enterprise.funds(),
+            enterprise.fuel(),
+        );
+    }
+}
+
+impl SimpleState for HeadlessState {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        // Sprites are never drawn, but gameplay code still hands out sprite sheet handles.
+        data.world.insert(AssetStorage::<Texture>::default());
+        data.world.insert(AssetStorage::<SpriteSheet>::default());
+        data.world.insert(ScreenDimensions::new(1024, 768, 1.0));
+        data.world.register::<SpriteRender>();
+        data.world.register::<Camera>();
+        data.world.register::<Billboard>();
+
+        let mut progress_counter = ProgressCounter::new();
+        let sprites = load_spritesheet(data.world, "Sprites", &mut ProgressCounter::new());
+        let level = load_level(data.world, self.level_path.clone(), &mut progress_counter);
+        let chemistry = load_chemistry(data.world, "reactions.ron", &mut progress_counter);
+        let materials = load_materials(data.world, "materials.ron", &mut progress_counter);
+        data.world.insert(SpriteStorage { sprites });
+        data.world.insert(ChemistryStorage { chemistry });
+        data.world.insert(MaterialStorage { materials });
+        data.world.insert(Enterprise::begin_enterprise());
+        data.world.insert(StartupSeed(self.seed));
+        data.world
+            .insert(std::mem::replace(&mut self.replay, ReplayMode::Live));
+
+        self.progress = Some(progress_counter);
+        self.level = Some(level);
+    }
+
+    fn update(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+        data.world
+            .write_resource::<Time>()
+            .set_delta_seconds(HEADLESS_FRAME);
+        if !self.started {
+            if let Some(progress) = &self.progress {
+                if progress.is_complete() {
+                    prepare_replay(data.world);
+                    initialize_level(data.world, self.level.as_ref().unwrap());
+                    begin_replay(data.world);
+                    self.started = true;
+                } else if progress.num_failed() > 0 {
+                    for error in progress.errors() {
+                        eprintln!("{}: {:?}", error.asset_name, error.error);
+                    }
+                    return Trans::Quit;
+                }
+            }
+            return Trans::None;
+        }
+        let finished = data.world.exec(|deliveries: ReadStorage<DeliveryZone>| {
+            (&deliveries)
+                .join()
+                .find(|delivery| delivery.jumped())
+                .is_some()
+        }) || data.world.read_resource::<Enterprise>().fuel() <= 0.0
+            || data
+                .world
+                .read_resource::<LevelTimer>()
+                .expired(&data.world.read_resource::<Level>());
+        if finished || self.elapsed >= self.frames {
+            if let Some(verdict) = finish_replay(data.world) {
+                println!("{}", verdict);
+            }
+            self.report(data.world);
+            return Trans::Quit;
+        }
+        next_input(data.world);
+        self.elapsed += 1;
+        Trans::None
+    }
+}
+
+pub fn run_headless(
+    level_path: String,
+    frames: usize,
+    seed: Option<u64>,
+    replay: ReplayMode,
+) -> amethyst::Result<()> {
+    let assets_dir = application_root_dir()?.join("assets/");
+    if let ReplayMode::Playback(replay) = &replay {
+        let level: Level = ron::de::from_reader(File::open(assets_dir.join(&level_path))?)?;
+        if level.reference.name != replay.level {
+            return Err(amethyst::Error::from_string(format!(
+                "the replay is of {}, but {} is {}",
+                replay.level, level_path, level.reference.name
+            )));
+        }
+    }
+    let game_data = with_gameplay_systems(GameDataBuilder::default())?;
+    let state = HeadlessState::new(level_path, frames, seed).with_replay(replay);
+    let mut game = Application::build(assets_dir, state)?
+        .with_frame_limit(FrameRateLimitStrategy::Unlimited, 0)
+        .build(game_data)?;
+    game.run();
+    Ok(())
+}
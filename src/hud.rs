@@ -0,0 +1,304 @@
+use amethyst::{
+    core::{HiddenPropagate, SystemBundle, Time},
+    ecs::*,
+    prelude::*,
+    renderer::SpriteRender,
+    ui::{UiImage, UiText, UiTransform},
+    Error,
+};
+
+use crate::{
+    achievements::AchievementToasts,
+    assets::{SpriteHandles, SpriteRes},
+    economy::{Enterprise, Trend},
+    level::{Level, LevelTimer},
+    materials::Materials,
+    menu::find_by_id,
+};
+
+pub struct MoneyHudSystem;
+impl MoneyHudSystem {
+    fn clock(seconds: f32) -> String {
+        let seconds = seconds.ceil() as u32;
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+
+    fn trend_sign(trend: Trend) -> &'static str {
+        match trend {
+            Trend::Rising => "+",
+            Trend::Steady => "=",
+            Trend::Falling => "-",
+        }
+    }
+
+    fn symbol<'s>(sprites: &SpriteRes<'s>) -> UiImage {
+        UiImage::Sprite(SpriteRender {
+            sprite_sheet: sprites.get_handle(),
+            sprite_number: 26,
+        })
+    }
+
+    fn digit<'s>(sprites: &SpriteRes<'s>, idx: usize, value: u64) -> UiImage {
+        let place = (10 as u64).pow(idx as u32);
+        let mut digit = 16 + ((value / place) % 10);
+        if value < place {
+            digit = 15;
+        }
+        UiImage::Sprite(SpriteRender {
+            sprite_sheet: sprites.get_handle(),
+            sprite_number: digit as usize,
+        })
+    }
+
+    fn insufficient_funds<'s>(sprites: &SpriteRes<'s>, insufficient: bool) -> UiImage {
+        if insufficient {
+            UiImage::Sprite(SpriteRender {
+                sprite_sheet: sprites.get_handle(),
+                sprite_number: 30,
+            })
+        } else {
+            UiImage::SolidColor([0.0, 0.0, 0.0, 0.0])
+        }
+    }
+
+    fn sufficient_funds<'s>(sprites: &SpriteRes<'s>, sufficient: bool) -> UiImage {
+        if sufficient {
+            UiImage::Sprite(SpriteRender {
+                sprite_sheet: sprites.get_handle(),
+                sprite_number: 31,
+            })
+        } else {
+            UiImage::SolidColor([0.0, 0.0, 0.0, 0.0])
+        }
+    }
+}
+impl<'s> System<'s> for MoneyHudSystem {
+    type SystemData = (
+        Entities<'s>,
+        WriteStorage<'s, UiTransform>,
+        WriteStorage<'s, UiImage>,
+        WriteStorage<'s, UiText>,
+        WriteStorage<'s, HiddenPropagate>,
+        SpriteRes<'s>,
+        Read<'s, Level>,
+        Read<'s, Enterprise>,
+        Read<'s, Materials>,
+        Read<'s, LevelTimer>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            mut transforms,
+            mut images,
+            mut texts,
+            mut hiddens,
+            sprites,
+            level,
+            enterprise,
+            materials,
+            timer,
+        ): Self::SystemData,
+    ) {
+        if let Some(symbol) = find_by_id(&entities, &transforms, "insufficient_funds") {
+            images.insert(
+                symbol,
+                MoneyHudSystem::insufficient_funds(&sprites, enterprise.tried_jump()),
+            );
+        }
+        if let Some(symbol) = find_by_id(&entities, &transforms, "sufficient_funds") {
+            images.insert(
+                symbol,
+                MoneyHudSystem::sufficient_funds(&sprites, enterprise.can_jump(level.jump_cost)),
+            );
+        }
+        let summary = enterprise.level_summary();
+        if let Some(refueling) = find_by_id(&entities, &transforms, "refueling") {
+            if let Some(refueling_text) = texts.get_mut(refueling) {
+                refueling_text.text = format!(
+                    "Earned: {} - Contracts: {} - Jump: {} - Refueling: {}",
+                    summary.income,
+                    summary.contract_rewards as i64 - summary.contract_penalties as i64,
+                    summary.jump_fee,
+                    summary.refueling
+                );
+            }
+        }
+        if let Some(loan) = find_by_id(&entities, &transforms, "loan") {
+            if enterprise.debt() > 0 {
+                hiddens.remove(loan);
+            } else {
+                hiddens.insert(loan, HiddenPropagate::new());
+            }
+            if let Some(loan_text) = texts.get_mut(loan) {
+                loan_text.text = if summary.loans > 0 {
+                    format!("You had to take out a loan! You owe: {}", enterprise.debt())
+                } else {
+                    format!(
+                        "You owe: {} - Interest: {} - Repaid: {}",
+                        enterprise.debt(),
+                        summary.interest,
+                        summary.repaid
+                    )
+                };
+            }
+        }
+        if let Some(bankruptcies) = find_by_id(&entities, &transforms, "bankruptcies") {
+            if let Some(bankruptcies_text) = texts.get_mut(bankruptcies) {
+                bankruptcies_text.text =
+                    format!("Bankruptcies so far: {}", enterprise.bankruptcies());
+            }
+        }
+        if let Some(fuel_cost) = find_by_id(&entities, &transforms, "fuel_cost") {
+            if let Some(fuel_cost) = texts.get_mut(fuel_cost) {
+                fuel_cost.text = format!(
+                    "Cost to jump: {} - Current fuel cost: {}",
+                    level.jump_cost,
+                    enterprise.refueling_cost(level.fuel_price())
+                );
+            }
+        }
+        let market = enterprise.market();
+        let mut prices = Vec::new();
+        for (idx, asteroid) in level.reference.shown_prices.iter().enumerate() {
+            let price = market.price(asteroid, level.get_ppm(&materials, asteroid));
+            let sign = MoneyHudSystem::trend_sign(market.trend(asteroid));
+            prices.push(format!("{:?} {:.2} {}", asteroid, price, sign));
+            let trend_id = format!("price_reference_{}_trend", idx);
+            if let Some(trend) = find_by_id(&entities, &transforms, &trend_id) {
+                if let Some(trend_text) = texts.get_mut(trend) {
+                    trend_text.text = format!("{:.2} {}", price, sign);
+                }
+            }
+        }
+        if let Some(market_label) = find_by_id(&entities, &transforms, "market") {
+            if let Some(market_text) = texts.get_mut(market_label) {
+                market_text.text = format!("Market: {}", prices.join(", "));
+            }
+        }
+        if let Some(news_label) = find_by_id(&entities, &transforms, "market_news") {
+            if let Some(news_text) = texts.get_mut(news_label) {
+                news_text.text = match market.news() {
+                    Some(news) if news.factor > 1.0 => format!(
+                        "News: {:?} prices are up {:.0}%!",
+                        news.asteroid,
+                        (news.factor - 1.0) * 100.0
+                    ),
+                    Some(news) => format!(
+                        "News: {:?} prices are down {:.0}%!",
+                        news.asteroid,
+                        (1.0 - news.factor) * 100.0
+                    ),
+                    None => String::new(),
+                };
+            }
+        }
+        if let Some(timer_label) = find_by_id(&entities, &transforms, "timer") {
+            if let Some(timer_text) = texts.get_mut(timer_label) {
+                let mut text = match timer.remaining(&level) {
+                    Some(remaining) => format!("Time left: {}", MoneyHudSystem::clock(remaining)),
+                    None => format!("Time: {}", MoneyHudSystem::clock(timer.elapsed)),
+                };
+                if let Some(par_time) = level.par_time {
+                    text += &format!(" - Par: {}", MoneyHudSystem::clock(par_time));
+                }
+                timer_text.text = text;
+            }
+        }
+        if let Some(score_label) = find_by_id(&entities, &transforms, "score") {
+            if let Some(score_text) = texts.get_mut(score_label) {
+                let best = enterprise
+                    .best_score(&level.reference.name)
+                    .unwrap_or_default();
+                score_text.text = match enterprise.latest_score() {
+                    Some(score) => format!("Score: {} - Best: {}", score, best),
+                    None => "No score, the level wasn't completed".to_string(),
+                };
+            }
+        }
+        if let Some(contracts) = find_by_id(&entities, &transforms, "contracts") {
+            if let Some(contracts_text) = texts.get_mut(contracts) {
+                contracts_text.text = enterprise
+                    .contracts()
+                    .iter()
+                    .map(|progress| progress.describe())
+                    .collect::<Vec<_>>()
+                    .join("\n");
+            }
+        }
+        if let Some(symbol) = find_by_id(&entities, &transforms, "money_symbol") {
+            images.insert(symbol, MoneyHudSystem::symbol(&sprites));
+        }
+        if let Some(fuel_level) = find_by_id(&entities, &transforms, "fuel_value") {
+            if let Some(fuel_level) = transforms.get_mut(fuel_level) {
+                fuel_level.width = (180.0 * enterprise.fuel() / 100.0) as f32;
+            }
+        }
+        for idx in 0..11 {
+            if let Some(digit) =
+                find_by_id(&entities, &transforms, format!("money_{}", idx).as_ref())
+            {
+                images.insert(
+                    digit,
+                    MoneyHudSystem::digit(&sprites, idx, enterprise.funds()),
+                );
+            }
+        }
+    }
+}
+
+pub struct InsufficientFundsWarningSystem;
+impl<'s> System<'s> for InsufficientFundsWarningSystem {
+    type SystemData = (Write<'s, Enterprise>, Read<'s, Time>);
+
+    fn run(&mut self, (mut enterprise, time): Self::SystemData) {
+        enterprise.cool_down_warning(time.delta_seconds());
+    }
+}
+
+/// Shows newly unlocked achievements one at a time, each for a few seconds.
+pub struct AchievementToastSystem;
+impl<'s> System<'s> for AchievementToastSystem {
+    type SystemData = (
+        Entities<'s>,
+        WriteStorage<'s, UiTransform>,
+        WriteStorage<'s, UiText>,
+        Write<'s, AchievementToasts>,
+        Read<'s, Time>,
+    );
+
+    fn run(&mut self, (entities, transforms, mut texts, mut toasts, time): Self::SystemData) {
+        if let Some((_, remaining)) = toasts.queue.first_mut() {
+            *remaining -= time.delta_seconds();
+            if *remaining <= 0.0 {
+                toasts.queue.remove(0);
+            }
+        }
+        if let Some(toast) = find_by_id(&entities, &transforms, "achievement_toast") {
+            if let Some(text) = texts.get_mut(toast) {
+                text.text = toasts.queue.first().map_or(String::new(), |(title, _)| {
+                    format!("Achievement unlocked: {}", title)
+                });
+            }
+        }
+    }
+}
+
+pub struct HudBundle;
+impl<'a, 'b> SystemBundle<'a, 'b> for HudBundle {
+    fn build(
+        self,
+        _world: &mut World,
+        dispatcher: &mut DispatcherBuilder<'a, 'b>,
+    ) -> Result<(), Error> {
+        dispatcher.add(MoneyHudSystem, "money_hud", &[]);
+        dispatcher.add(
+            InsufficientFundsWarningSystem,
+            "insufficient_funds_cooldown",
+            &[],
+        );
+        dispatcher.add(AchievementToastSystem, "achievement_toast", &[]);
+        Ok(())
+    }
+}
@@ -1,13 +1,14 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, ops::Deref};
 
 use amethyst::{
     assets::{Asset, AssetStorage, Handle, ProcessableAsset, ProcessingState},
-    core::{math::Vector3, HiddenPropagate, SystemBundle, Transform},
+    core::{math::Vector3, HiddenPropagate, SystemBundle, Time, Transform},
     ecs::*,
     prelude::*,
     renderer::SpriteRender,
     shrev::EventChannel,
     ui::{UiButtonAction, UiEvent, UiEventType, UiImage, UiText, UiTransform},
+    utils::application_root_dir,
     Error,
 };
 use nalgebra::Vector2;
@@ -17,21 +18,44 @@ use ncollide2d::{
     shape::{Cuboid, ShapeHandle},
 };
 use nphysics2d::object::{BodyStatus, ColliderDesc, RigidBodyDesc};
+use rand::Rng;
 
 use crate::{
-    assets::{LevelStorage, SpriteHandles, SpriteRes, SpriteStorage},
-    asteroid::{generate_asteroid_field, Asteroid, AsteroidType},
+    assets::{
+        ChemistryStorage, LevelStorage, MaterialStorage, SpriteHandles, SpriteRes, SpriteStorage,
+    },
+    asteroid::{
+        generate_asteroid_field, generate_moving_asteroid, Asteroid, AsteroidStream, AsteroidType,
+        FieldMix, FieldMotion,
+    },
     billboards::{generate_billboard, BillboardDesc},
+    chemistry::{Chemistry, ExplosionDesc, ReactionDesc},
     delivery::{generate_delivery_zone, DeliveryAnimationSystem},
-    economy::Enterprise,
+    economy::{Contract, Enterprise, Unlock, DEFAULT_FUEL_PRICE},
+    events::JumpStarted,
+    materials::Materials,
     menu::{find_by_id, CardDesc},
     particles::random_direction,
-    physics::{Physics, PhysicsDesc, PhysicsHandle, PhysicsProximityEvent},
+    physics::{Physics, PhysicsClock, PhysicsDesc, PhysicsHandle, PhysicsProximityEvent},
+    placement::{Area, Placement},
     player::initialize_player,
+    rng::{GameRng, StartupSeed},
+    scripting::LevelScript,
+    tutorial::{Action, Trigger, TutorialProgress, TutorialStep},
 };
 
+/// How far randomly placed asteroids keep from the player's start and delivery zones by default.
+pub const DEFAULT_KEEP_OUT: f32 = 100.0;
+/// How much of a field scattered asteroids can be counted on to cover before placement starts
+/// giving up on them.
+const FIELD_PACKING: f32 = 0.5;
+
 #[derive(Serialize, Deserialize, Clone)]
 pub enum AsteroidDesc {
+    /// Asteroids scattered evenly over an area, by default a rectangle around `location` or the
+    /// whole level. The counts are a preset for the usual kinds
+    /// of asteroid, and `mix` adds weighted tables of any others. A field with no mix holds one
+    /// encased artifact unless it says otherwise.
     Field {
         location: Option<(f32, f32, f32, f32)>,
         normal: Option<usize>,
@@ -40,41 +64,527 @@ pub enum AsteroidDesc {
         sulphur: Option<usize>,
         artifacts: Option<usize>,
         debris: Option<(usize, f32)>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        mix: Vec<FieldMix>,
+        /// Any shape to scatter over instead of the `location` rectangle.
+        area: Option<Area>,
+        /// The range of speeds the asteroids start with, each heading in a random direction.
+        speed: Option<(f32, f32)>,
+        /// The range of spins they start with, in radians a second.
+        spin: Option<(f32, f32)>,
+    },
+    /// One asteroid placed exactly, optionally already moving and turning.
+    Single {
+        asteroid: AsteroidType,
+        position: (f32, f32),
+        velocity: Option<(f32, f32)>,
+        spin: Option<f32>,
+    },
+    /// Asteroids spaced evenly around a circle, taking their types from `types` in turn.
+    Ring {
+        center: (f32, f32),
+        radius: f32,
+        count: usize,
+        types: Vec<AsteroidType>,
+    },
+    /// Asteroids bunched around a centre and thinning out away from it, `spread` being the
+    /// standard deviation of their distance.
+    Cluster {
+        center: (f32, f32),
+        spread: f32,
+        count: usize,
+        types: Vec<AsteroidType>,
+    },
+    /// Keeps launching asteroids from `origin` at `velocity`, `rate` a second, for the whole
+    /// level or until `limit` have been launched.
+    Stream {
+        origin: (f32, f32),
+        velocity: (f32, f32),
+        rate: f32,
+        types: Vec<AsteroidType>,
+        limit: Option<usize>,
     },
 }
 
+impl AsteroidDesc {
+    /// What a field is made of: its preset counts, then its own mixes.
+    pub fn field_mixes(&self) -> Vec<FieldMix> {
+        match self {
+            AsteroidDesc::Field {
+                normal,
+                bombs,
+                gases,
+                sulphur,
+                artifacts,
+                debris,
+                mix,
+                ..
+            } => {
+                let mut mixes = FieldMix::preset(
+                    normal.unwrap_or_default(),
+                    bombs.unwrap_or_default(),
+                    gases.unwrap_or_default(),
+                    sulphur.unwrap_or_default(),
+                    artifacts.unwrap_or(if mix.is_empty() { 1 } else { 0 }),
+                    debris.unwrap_or_default(),
+                );
+                mixes.extend(mix.iter().cloned());
+                mixes
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Where a field scatters its asteroids, for a level with the given boundaries.
+    pub fn field_area(&self, boundaries: (f32, f32)) -> Option<Area> {
+        match self {
+            AsteroidDesc::Field {
+                area: Some(area), ..
+            } => Some(area.clone()),
+            AsteroidDesc::Field { location, .. } => {
+                let (x, y, width, height) =
+                    location.unwrap_or((0.0, 0.0, boundaries.0, boundaries.1));
+                Some(Area::Centered {
+                    center: (x, y),
+                    size: (width, height),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// The asteroid types this places by name, leaving out a field's preset ones.
+    fn types(&self) -> Vec<&AsteroidType> {
+        match self {
+            AsteroidDesc::Field { mix, .. } => mix
+                .iter()
+                .flat_map(|mix| mix.types.iter().map(|(asteroid, _)| asteroid))
+                .collect(),
+            AsteroidDesc::Single { asteroid, .. } => vec![asteroid],
+            AsteroidDesc::Ring { types, .. }
+            | AsteroidDesc::Cluster { types, .. }
+            | AsteroidDesc::Stream { types, .. } => types.iter().collect(),
+        }
+    }
+}
+
+/// Evenly spaced points around a circle, starting on its right.
+fn ring_positions(center: (f32, f32), radius: f32, count: usize) -> Vec<(f32, f32)> {
+    (0..count)
+        .map(|index| {
+            let angle = index as f32 / count as f32 * std::f32::consts::PI * 2.0;
+            (
+                center.0 + radius * angle.cos(),
+                center.1 + radius * angle.sin(),
+            )
+        })
+        .collect()
+}
+
+/// A point drawn from a normal distribution around the origin, by the Box-Muller transform.
+fn gaussian(rng: &mut impl Rng, deviation: f32) -> (f32, f32) {
+    let radius = (-2.0 * (1.0 - rng.gen::<f32>()).ln()).sqrt() * deviation;
+    let angle = rng.gen::<f32>() * std::f32::consts::PI * 2.0;
+    (radius * angle.cos(), radius * angle.sin())
+}
+
 #[derive(Serialize, Deserialize, Clone, Default)]
 pub struct ReferenceDesc {
     pub name: String,
-    description: String,
-    shown_prices: Vec<AsteroidType>,
+    pub(crate) description: String,
+    pub shown_prices: Vec<AsteroidType>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Default)]
 pub struct Level {
-    boundaries: (f32, f32),
-    player_start: Option<(f32, f32)>,
-    deliveries: Vec<(f32, f32)>,
+    pub(crate) boundaries: (f32, f32),
+    pub(crate) player_start: Option<(f32, f32)>,
+    pub(crate) deliveries: Vec<(f32, f32)>,
     pub jump_cost: u64,
     pub card: CardDesc,
-    asteroids: Vec<AsteroidDesc>,
-    billboards: Vec<BillboardDesc>,
-    modified_prices: Option<HashMap<AsteroidType, f32>>,
+    pub(crate) asteroids: Vec<AsteroidDesc>,
+    pub(crate) billboards: Vec<BillboardDesc>,
+    pub(crate) modified_prices: Option<HashMap<AsteroidType, f32>>,
+    reactions: Option<HashMap<String, Option<ReactionDesc>>>,
+    explosions: Option<HashMap<String, Option<ExplosionDesc>>>,
     pub reference: ReferenceDesc,
+    pub(crate) seed: Option<u64>,
+    /// How far randomly placed asteroids keep from the player's start and the delivery zones.
+    keep_out: Option<f32>,
+    /// Extra room left between randomly placed asteroids.
+    spacing: Option<f32>,
+    #[serde(default)]
+    pub unlock: Unlock,
+    fuel_price: Option<f64>,
+    #[serde(default)]
+    pub contracts: Vec<Contract>,
+    /// Seconds before the level ends on its own.
+    pub time_limit: Option<f32>,
+    /// Seconds a good run takes; finishing sooner raises the score.
+    pub par_time: Option<f32>,
+    #[serde(default)]
+    pub tutorial: Vec<TutorialStep>,
+    /// A Rhai script for the level, relative to the assets directory.
+    pub script: Option<String>,
 }
 
 impl Level {
-    pub fn get_ppm(&self, asteroid_type: AsteroidType) -> f32 {
+    pub fn fuel_price(&self) -> f64 {
+        self.fuel_price.unwrap_or(DEFAULT_FUEL_PRICE)
+    }
+
+    /// Changes what an asteroid sells for here, for the rest of the level.
+    pub fn set_price(&mut self, asteroid_type: AsteroidType, ppm: f32) {
+        self.modified_prices
+            .get_or_insert_with(HashMap::new)
+            .insert(asteroid_type, ppm);
+    }
+
+    /// Goes back to the catalogue price for an asteroid.
+    pub fn reset_price(&mut self, asteroid_type: &AsteroidType) {
+        if let Some(modified_prices) = &mut self.modified_prices {
+            modified_prices.remove(asteroid_type);
+        }
+    }
+
+    /// The room random placements have to work with: clear of the player's start and the
+    /// delivery zones, and of every asteroid placed exactly.
+    pub fn placement(&self, materials: &Materials) -> Placement {
+        let mut placement = Placement::new(self.spacing.unwrap_or_default());
+        let keep_out = self.keep_out.unwrap_or(DEFAULT_KEEP_OUT);
+        placement.keep_out(self.player_start.unwrap_or((0.0, 0.0)), keep_out);
+        for delivery in self.deliveries.iter() {
+            placement.keep_out(*delivery, keep_out);
+        }
+        for asteroid_desc in self.asteroids.iter() {
+            match asteroid_desc {
+                AsteroidDesc::Single {
+                    asteroid, position, ..
+                } => placement.occupy(*position, materials.get(asteroid).radius),
+                AsteroidDesc::Ring {
+                    center,
+                    radius,
+                    count,
+                    types,
+                } => {
+                    for (position, asteroid) in ring_positions(*center, *radius, *count)
+                        .into_iter()
+                        .zip(types.iter().cycle())
+                    {
+                        placement.occupy(position, materials.get(asteroid).radius);
+                    }
+                }
+                _ => {}
+            }
+        }
+        placement
+    }
+
+    pub fn get_ppm(&self, materials: &Materials, asteroid_type: &AsteroidType) -> f32 {
         self.modified_prices
             .as_ref()
-            .and_then(|modified_prices| modified_prices.get(&asteroid_type))
+            .and_then(|modified_prices| modified_prices.get(asteroid_type))
             .cloned()
-            .unwrap_or(asteroid_type.get_base_ppm())
+            .unwrap_or_else(|| materials.get(asteroid_type).base_ppm)
+    }
+
+    /// Describes everything in this level that won't play out as written: things placed outside
+    /// the boundaries, sprites the sheet doesn't have and materials the catalogue doesn't know.
+    pub fn problems(&self, sprite_count: usize, materials: &Materials) -> Vec<String> {
+        let mut problems = Vec::new();
+        let (width, height) = self.boundaries;
+        let inside = |(x, y): (f32, f32)| x.abs() <= width && y.abs() <= height;
+        if let Some(player_start) = self.player_start {
+            if !inside(player_start) {
+                problems.push(format!(
+                    "player start {:?} is outside the boundaries",
+                    player_start
+                ));
+            }
+        }
+        for delivery in self.deliveries.iter() {
+            if !inside(*delivery) {
+                problems.push(format!(
+                    "delivery zone {:?} is outside the boundaries",
+                    delivery
+                ));
+            }
+        }
+        if self.deliveries.is_empty() {
+            problems.push("there are no delivery zones".to_string());
+        }
+        for asteroid_desc in self.asteroids.iter() {
+            match asteroid_desc {
+                AsteroidDesc::Field {
+                    location,
+                    mix,
+                    area,
+                    ..
+                } => {
+                    if let Some(area) = area {
+                        let ((min_x, min_y), (max_x, max_y)) = area.bounds();
+                        if min_x < -width || min_y < -height || max_x > width || max_y > height {
+                            problems.push(format!("field {:?} reaches past the boundaries", area));
+                        }
+                        if let Area::Polygon(vertices) = area {
+                            if vertices.len() < 3 {
+                                problems.push(format!(
+                                    "field polygon {:?} needs at least three vertices",
+                                    vertices
+                                ));
+                            }
+                        }
+                    }
+                    for mix in mix.iter() {
+                        if mix.types.iter().all(|(_, weight)| *weight <= 0.0) {
+                            problems.push(format!(
+                                "a field mix of {:?} has no positive weights",
+                                mix.amount
+                            ));
+                        }
+                    }
+                    let field_area = asteroid_desc.field_area(self.boundaries).unwrap();
+                    let spacing = self.spacing.unwrap_or_default();
+                    let needed: f32 = asteroid_desc
+                        .field_mixes()
+                        .iter()
+                        .map(|mix| {
+                            mix.amount.count(field_area.area()) as f32
+                                * mix.footprint(materials, spacing)
+                        })
+                        .sum();
+                    if needed > field_area.area() * FIELD_PACKING {
+                        problems.push(format!(
+                            "field around {:?} is too crowded for its asteroids to fit {} apart",
+                            field_area.center(),
+                            spacing
+                        ));
+                    }
+                    if let (None, Some((x, y, field_width, field_height))) = (area, location) {
+                        if x.abs() + field_width / 2.0 > width
+                            || y.abs() + field_height / 2.0 > height
+                        {
+                            problems.push(format!(
+                                "field {:?} reaches past the boundaries",
+                                (x, y, field_width, field_height)
+                            ));
+                        }
+                    }
+                }
+                AsteroidDesc::Single {
+                    asteroid, position, ..
+                } => {
+                    if !inside(*position) {
+                        problems.push(format!(
+                            "{:?} at {:?} is outside the boundaries",
+                            asteroid, position
+                        ));
+                    }
+                }
+                AsteroidDesc::Ring { center, radius, .. } => {
+                    if center.0.abs() + radius > width || center.1.abs() + radius > height {
+                        problems.push(format!(
+                            "ring at {:?} with radius {} reaches past the boundaries",
+                            center, radius
+                        ));
+                    }
+                }
+                AsteroidDesc::Cluster { center, spread, .. } => {
+                    // Nearly everything a cluster scatters lands within three spreads of it.
+                    let reach = 3.0 * spread;
+                    if !inside((center.0 - reach, center.1 - reach))
+                        || !inside((center.0 + reach, center.1 + reach))
+                    {
+                        problems.push(format!(
+                            "cluster at {:?} spreads past the boundaries",
+                            center
+                        ));
+                    }
+                }
+                AsteroidDesc::Stream {
+                    origin,
+                    velocity,
+                    rate,
+                    types,
+                    ..
+                } => {
+                    if !inside(*origin) {
+                        problems.push(format!("stream at {:?} is outside the boundaries", origin));
+                    }
+                    if *rate <= 0.0 {
+                        problems.push(format!("stream at {:?} never launches anything", origin));
+                    } else {
+                        let spacing = velocity.0.hypot(velocity.1) / rate;
+                        let widest = types
+                            .iter()
+                            .filter(|asteroid| materials.contains(asteroid))
+                            .map(|asteroid| materials.get(asteroid).radius * 2.0)
+                            .fold(0.0, f32::max);
+                        if spacing < widest {
+                            problems.push(format!(
+                                "stream at {:?} launches asteroids {} apart, closer than their {} width",
+                                origin, spacing, widest
+                            ));
+                        }
+                    }
+                }
+            }
+            if !matches!(
+                asteroid_desc,
+                AsteroidDesc::Field { .. } | AsteroidDesc::Single { .. }
+            ) && asteroid_desc.types().is_empty()
+            {
+                problems.push("a ring, cluster or stream has no asteroid types".to_string());
+            }
+            for asteroid in asteroid_desc.types() {
+                if !materials.contains(asteroid) {
+                    problems.push(format!(
+                        "{:?} is placed but is not in the materials catalogue",
+                        asteroid
+                    ));
+                }
+            }
+        }
+        let mut sprites = vec![("card", self.card.sprite_number())];
+        for billboard in self.billboards.iter() {
+            sprites.push(("billboard", billboard.sprite_number));
+        }
+        for (index, step) in self.tutorial.iter().enumerate() {
+            if let Trigger::Reach { location, .. } = step.trigger {
+                if !inside(location) {
+                    problems.push(format!(
+                        "tutorial step {} waits at {:?}, outside the boundaries",
+                        index, location
+                    ));
+                }
+            }
+            for action in step.actions.iter() {
+                match action {
+                    Action::Billboard(billboard) => {
+                        sprites.push(("tutorial billboard", billboard.sprite_number))
+                    }
+                    Action::Spawn { asteroid, location } => {
+                        if !inside(*location) {
+                            problems.push(format!(
+                                "tutorial step {} spawns {:?} outside the boundaries",
+                                index, asteroid
+                            ));
+                        }
+                        if !materials.contains(asteroid) {
+                            problems.push(format!(
+                                "tutorial step {} spawns {:?}, which is not in the materials catalogue",
+                                index, asteroid
+                            ));
+                        }
+                    }
+                    Action::Text(_) | Action::Highlight(_) => {}
+                }
+            }
+        }
+        for (what, sprite_number) in sprites {
+            if sprite_number >= sprite_count {
+                problems.push(format!(
+                    "{} sprite {} is not in the sprite sheet ({} sprites)",
+                    what, sprite_number, sprite_count
+                ));
+            }
+        }
+        for asteroid_type in self.reference.shown_prices.iter() {
+            if !materials.contains(asteroid_type) {
+                problems.push(format!(
+                    "shown price for {:?} is not in the materials catalogue",
+                    asteroid_type
+                ));
+            }
+        }
+        for (what, time) in [("time limit", self.time_limit), ("par time", self.par_time)] {
+            if time.map_or(false, |time| time <= 0.0) {
+                problems.push(format!("{} {:?} is not positive", what, time.unwrap()));
+            }
+        }
+        if let (Some(time_limit), Some(par_time)) = (self.time_limit, self.par_time) {
+            if par_time > time_limit {
+                problems.push(format!(
+                    "par time {} is longer than the time limit {}",
+                    par_time, time_limit
+                ));
+            }
+        }
+        for contract in self.contracts.iter() {
+            if !materials.contains(contract.asteroid()) {
+                problems.push(format!(
+                    "contract {:?} is for something not in the materials catalogue",
+                    contract.describe()
+                ));
+            }
+        }
+        if let Some(modified_prices) = &self.modified_prices {
+            for asteroid_type in modified_prices.keys() {
+                if !materials.contains(asteroid_type) {
+                    problems.push(format!(
+                        "modified price for {:?} is not in the materials catalogue",
+                        asteroid_type
+                    ));
+                }
+            }
+        }
+        problems
     }
 }
 
 pub type LevelHandle = Handle<Level>;
 
+/// Time spent in the level in progress, up to the start of the jump out.
+#[derive(Default, Clone, Copy)]
+pub struct LevelTimer {
+    pub elapsed: f32,
+    pub stopped: bool,
+}
+
+impl LevelTimer {
+    pub fn remaining(&self, level: &Level) -> Option<f32> {
+        level
+            .time_limit
+            .map(|time_limit| f32::max(0.0, time_limit - self.elapsed))
+    }
+
+    pub fn expired(&self, level: &Level) -> bool {
+        self.remaining(level) == Some(0.0)
+    }
+}
+
+#[derive(Default)]
+pub struct LevelTimerSystem {
+    jumped: Option<ReaderId<JumpStarted>>,
+}
+
+impl<'s> System<'s> for LevelTimerSystem {
+    type SystemData = (
+        Write<'s, LevelTimer>,
+        Read<'s, EventChannel<JumpStarted>>,
+        Read<'s, Time>,
+    );
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.jumped = Some(
+            world
+                .write_resource::<EventChannel<JumpStarted>>()
+                .register_reader(),
+        );
+    }
+
+    fn run(&mut self, (mut timer, jumped, time): Self::SystemData) {
+        // The jump animation can't run the clock out.
+        if jumped.read(self.jumped.as_mut().unwrap()).next().is_some() {
+            timer.stopped = true;
+        }
+        if !timer.stopped {
+            timer.elapsed += time.delta_seconds();
+        }
+    }
+}
+
 impl Asset for Level {
     const NAME: &'static str = "ld49::Level";
     type Data = Level;
@@ -156,6 +666,32 @@ pub fn initialize_level(world: &mut World, level: &LevelHandle) {
             .cloned()
             .unwrap()
     };
+    let materials = {
+        let handle = world.read_resource::<MaterialStorage>().materials.clone();
+        world
+            .read_resource::<AssetStorage<Materials>>()
+            .get(&handle)
+            .cloned()
+            .unwrap()
+    };
+    world.insert(materials);
+    world.write_resource::<PhysicsClock>().reset();
+    world.insert(LevelTimer::default());
+    world.insert(TutorialProgress::default());
+    world.remove::<LevelScript>();
+    if let Some(path) = &level.script {
+        match application_root_dir()
+            .map_err(|error| error.to_string())
+            .and_then(|root| LevelScript::load(&root.join("assets"), path))
+        {
+            Ok(script) => world.insert(script),
+            Err(error) => log::error!("Could not load level script: {}", error),
+        }
+    }
+    let startup_seed = world
+        .try_fetch::<StartupSeed>()
+        .and_then(|startup_seed| startup_seed.0);
+    let mut rng = GameRng::new(startup_seed.or(level.seed));
     let mut transform = Transform::default();
     let (x, y) = level.player_start.unwrap_or((0.0, 0.0));
     transform.set_translation_x(x);
@@ -167,34 +703,92 @@ pub fn initialize_level(world: &mut World, level: &LevelHandle) {
         transform.set_translation_y(*y);
         generate_delivery_zone(world, (75.0, 75.0), transform);
     }
+    let (width, height) = level.boundaries;
+    let mut placement = level.placement(&world.read_resource::<Materials>());
     for asteroid_desc in &level.asteroids {
         match asteroid_desc {
-            AsteroidDesc::Field {
-                location,
-                normal,
-                bombs,
-                gases,
-                sulphur,
-                artifacts,
-                debris,
-            } => {
-                let mut transform = Transform::default();
-                let location =
-                    location.unwrap_or((0.0, 0.0, level.boundaries.0, level.boundaries.1));
-                transform.set_translation_x(location.0);
-                transform.set_translation_y(location.1);
+            AsteroidDesc::Field { speed, spin, .. } => {
+                let area = asteroid_desc.field_area(level.boundaries).unwrap();
                 generate_asteroid_field(
                     world,
-                    (location.2, location.3),
-                    normal.unwrap_or_default(),
-                    bombs.unwrap_or_default(),
-                    gases.unwrap_or_default(),
-                    sulphur.unwrap_or_default(),
-                    artifacts.unwrap_or(1),
-                    debris.unwrap_or_default(),
-                    transform,
+                    &mut rng,
+                    &area,
+                    &asteroid_desc.field_mixes(),
+                    FieldMotion {
+                        speed: speed.unwrap_or_default(),
+                        spin: spin.unwrap_or_default(),
+                    },
+                    &mut placement,
+                );
+            }
+            AsteroidDesc::Single {
+                asteroid,
+                position,
+                velocity,
+                spin,
+            } => {
+                let (vx, vy) = velocity.unwrap_or_default();
+                place_asteroid(
+                    world,
+                    asteroid.clone(),
+                    *position,
+                    Vector2::new(vx, vy),
+                    spin.unwrap_or_default(),
                 );
             }
+            AsteroidDesc::Ring {
+                center,
+                radius,
+                count,
+                types,
+            } => {
+                for (position, asteroid) in ring_positions(*center, *radius, *count)
+                    .into_iter()
+                    .zip(types.iter().cycle())
+                {
+                    place_asteroid(world, asteroid.clone(), position, Vector2::zeros(), 0.0);
+                }
+            }
+            AsteroidDesc::Cluster {
+                center,
+                spread,
+                count,
+                types,
+            } => {
+                if !types.is_empty() {
+                    for _ in 0..*count {
+                        let asteroid = types[rng.gen_range(0..types.len())].clone();
+                        let radius = world.read_resource::<Materials>().get(&asteroid).radius;
+                        if let Some(position) = placement.place(&mut rng, radius, |rng| {
+                            let (x, y) = gaussian(rng, *spread);
+                            let (x, y) = (center.0 + x, center.1 + y);
+                            (x.abs() <= width && y.abs() <= height).then(|| (x, y))
+                        }) {
+                            place_asteroid(world, asteroid, position, Vector2::zeros(), 0.0);
+                        }
+                    }
+                }
+            }
+            AsteroidDesc::Stream {
+                origin,
+                velocity,
+                rate,
+                types,
+                limit,
+            } => {
+                let mut transform = Transform::default();
+                transform.set_translation_xyz(origin.0, origin.1, 0.0);
+                world
+                    .create_entity()
+                    .with(transform)
+                    .with(AsteroidStream::new(
+                        Vector2::new(velocity.0, velocity.1),
+                        *rate,
+                        types.clone(),
+                        *limit,
+                    ))
+                    .build();
+            }
         }
     }
     for billboard_desc in &level.billboards {
@@ -205,9 +799,41 @@ pub fn initialize_level(world: &mut World, level: &LevelHandle) {
         generate_billboard(world.create_entity(), spritesheet, billboard_desc);
     }
     generate_boundaries(world, level.boundaries);
+    let chemistry = {
+        let handle = world.read_resource::<ChemistryStorage>().chemistry.clone();
+        world
+            .read_resource::<AssetStorage<Chemistry>>()
+            .get(&handle)
+            .cloned()
+            .unwrap()
+    };
+    world.insert(chemistry.with_overrides(&level.reactions, &level.explosions));
+    world.insert(rng);
     world.insert(level);
 }
 
+fn place_asteroid(
+    world: &mut World,
+    asteroid: AsteroidType,
+    (x, y): (f32, f32),
+    velocity: Vector2<f32>,
+    spin: f32,
+) {
+    let sprites = world.read_resource::<SpriteStorage>().sprites.clone();
+    let materials = { world.read_resource::<Materials>().deref().clone() };
+    let mut transform = Transform::default();
+    transform.set_translation_xyz(x, y, 0.0);
+    generate_moving_asteroid(
+        world.create_entity(),
+        sprites,
+        &materials,
+        asteroid,
+        transform,
+        velocity,
+        spin,
+    );
+}
+
 #[derive(Default)]
 pub struct AsteroidReintroductionSystem {
     reader: Option<ReaderId<PhysicsProximityEvent>>,
@@ -218,16 +844,17 @@ fn reintroduce(
     boundary: &Boundaries,
     handle: &PhysicsHandle,
     asteroid: Entity,
+    rng: &mut GameRng,
 ) {
-    let mut outer = random_direction();
+    let mut outer = random_direction(rng);
     outer /= outer.x.min(outer.y);
     outer.x *= boundary.width;
     outer.y *= boundary.height;
     let (x, y, vx, vy) = (
         outer.x,
         outer.y,
-        rand::random::<f32>() * -outer.x.signum(),
-        rand::random::<f32>() * -outer.y.signum(),
+        rng.gen::<f32>() * -outer.x.signum(),
+        rng.gen::<f32>() * -outer.y.signum(),
     );
     physics.set_location(&handle, x, y);
     let current_speed = physics.get_velocity(&handle).unwrap().magnitude().max(10.0);
@@ -249,9 +876,11 @@ impl<'s> System<'s> for AsteroidReintroductionSystem {
         Entities<'s>,
         Read<'s, EventChannel<PhysicsProximityEvent>>,
         Write<'s, Physics>,
+        Write<'s, GameRng>,
     );
 
     fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
         self.reader = Some(
             world
                 .write_resource::<EventChannel<PhysicsProximityEvent>>()
@@ -261,7 +890,7 @@ impl<'s> System<'s> for AsteroidReintroductionSystem {
 
     fn run(
         &mut self,
-        (asteroids, handles, boundaries, entities, events, mut physics): Self::SystemData,
+        (asteroids, handles, boundaries, entities, events, mut physics, mut rng): Self::SystemData,
     ) {
         if let Some(reader) = &mut self.reader {
             for ProximityEvent {
@@ -280,11 +909,23 @@ impl<'s> System<'s> for AsteroidReintroductionSystem {
                             if let (true, Some(boundary)) =
                                 (asteroids.contains(a), boundaries.get(b))
                             {
-                                reintroduce(&mut physics, boundary, handles.get(a).unwrap(), a);
+                                reintroduce(
+                                    &mut physics,
+                                    boundary,
+                                    handles.get(a).unwrap(),
+                                    a,
+                                    &mut rng,
+                                );
                             } else if let (true, Some(boundary)) =
                                 (asteroids.contains(b), boundaries.get(a))
                             {
-                                reintroduce(&mut physics, boundary, handles.get(b).unwrap(), b);
+                                reintroduce(
+                                    &mut physics,
+                                    boundary,
+                                    handles.get(b).unwrap(),
+                                    b,
+                                    &mut rng,
+                                );
                             }
                         }
                     }
@@ -310,9 +951,11 @@ impl<'s> System<'s> for ReferenceCardSystem {
         Read<'s, Level>,
         Read<'s, Enterprise>,
         SpriteRes<'s>,
+        Read<'s, Materials>,
     );
 
     fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
         self.reader = Some(
             world
                 .write_resource::<EventChannel<UiEvent>>()
@@ -332,6 +975,7 @@ impl<'s> System<'s> for ReferenceCardSystem {
             level,
             enterprise,
             sprites,
+            materials,
         ): Self::SystemData,
     ) {
         if let Some(reader) = &mut self.reader {
@@ -373,11 +1017,15 @@ impl<'s> System<'s> for ReferenceCardSystem {
                                 find_by_id(&entities, &transforms, &price_id),
                             ) {
                                 if let Some(asteroid) = level.reference.shown_prices.get(idx) {
-                                    let price = level.get_ppm(*asteroid).clamp(0.0, 7.0);
+                                    let material = materials.get(asteroid);
+                                    let price = enterprise
+                                        .market()
+                                        .price(asteroid, level.get_ppm(&materials, asteroid))
+                                        .clamp(0.0, 7.0);
                                     if let Some(asteroid_image) = images.get_mut(asteroid_ref) {
                                         *asteroid_image = UiImage::Sprite(SpriteRender::new(
                                             sprites.get_handle(),
-                                            asteroid.get_sprite_num(),
+                                            material.sprite_number,
                                         ));
                                     }
                                     if let (Some(price_image), Some(price_transform)) =
@@ -435,6 +1083,7 @@ impl<'a, 'b> SystemBundle<'a, 'b> for LevelBundle {
         dispatcher: &mut DispatcherBuilder<'a, 'b>,
     ) -> Result<(), Error> {
         dispatcher.add(DummySystem, "boundary_dummy", &[]);
+        dispatcher.add(LevelTimerSystem::default(), "level_timer", &[]);
         dispatcher.add(DeliveryAnimationSystem, "delivery_animation", &[]);
         dispatcher.add(ReferenceCardSystem::default(), "reference_card", &[]);
         dispatcher.add(
@@ -0,0 +1,248 @@
+#![allow(warnings)]
+#[macro_use]
+extern crate serde;
+use std::{
+    ops::Deref,
+    path::{Path, PathBuf},
+};
+
+use achievements::AchievementBundle;
+use amethyst::{
+    assets::{AssetStorage, Directory, Processor, ProgressCounter, Source},
+    audio::{output::init_output, AudioBundle},
+    core::{HideHierarchySystem, HideHierarchySystemDesc, Transform, TransformBundle},
+    ecs::*,
+    input::is_close_requested,
+    prelude::*,
+    renderer::{
+        types::DefaultBackend, Camera, RenderDebugLines, RenderFlat2D, RenderToWindow,
+        RenderingBundle,
+    },
+    tiles::RenderTiles2D,
+    ui::{RenderUi, UiBundle, UiCreator, UiEventType, UiFinder},
+    utils::{application_root_dir, fps_counter::FpsCounterBundle},
+    winit::{dpi::LogicalSize, Event, WindowEvent},
+    Application, GameData, GameDataBuilder, LogLevelFilter, LoggerConfig, SimpleState, SimpleTrans,
+    StateData, StateEvent, StdoutLog, Trans,
+};
+use assets::{
+    load_level, load_sound_file, load_spritesheet, ChemistryStorage, DjSystem, LevelStorage,
+    LoadingState, MaterialStorage, SoundStorage, SpriteStorage,
+};
+use asteroid::{generate_asteroid, AsteroidBundle, AsteroidType};
+use billboards::BillboardBundle;
+use chemistry::Chemistry;
+use delivery::DeliveryZone;
+use dev::restart_requested;
+use economy::{score, Enterprise};
+use level::{generate_boundaries, initialize_level, Level, LevelBundle, LevelHandle, LevelTimer};
+use materials::Materials;
+use particles::ParticleBundle;
+use physics::{PhysicsBundle, PhysicsHandle};
+use player::{initialize_player, PlayerBundle};
+use replay::{begin_replay, finish_replay, next_input, prepare_replay, Replay, ReplayMode};
+use rng::GameRng;
+use scripting::ScriptingBundle;
+use serde::Deserialize;
+use stats::{Statistics, StatsBundle};
+use tutorial::TutorialBundle;
+
+use crate::{
+    delivery::generate_delivery_zone,
+    menu::{CardDesc, MenuState, MenuTransition},
+};
+pub mod achievements;
+pub mod assets;
+pub mod asteroid;
+pub mod billboards;
+pub mod chemistry;
+pub mod delivery;
+pub mod dev;
+pub mod economy;
+pub mod editor;
+pub mod events;
+pub mod explosions;
+pub mod headless;
+pub mod hud;
+pub mod level;
+pub mod materials;
+pub mod menu;
+pub mod particles;
+pub mod physics;
+pub mod placement;
+pub mod player;
+pub mod procgen;
+pub mod replay;
+pub mod rng;
+pub mod save;
+pub mod scripting;
+pub mod stats;
+#[cfg(test)]
+pub mod test_support;
+pub mod tractor;
+pub mod tutorial;
+
+type ASSETS = (
+    SpriteStorage,
+    LevelStorage,
+    SoundStorage,
+    ChemistryStorage,
+    MaterialStorage,
+);
+
+struct GameplayState {
+    assets: ASSETS,
+    level: LevelHandle,
+    enterprise: Enterprise,
+}
+impl SimpleState for GameplayState {
+    fn on_start(&mut self, mut data: StateData<'_, GameData<'_, '_>>) {
+        data.world.delete_all();
+        data.world.insert(self.assets.0.clone());
+        data.world.insert(self.assets.1.clone());
+        data.world.insert(self.assets.2.clone());
+        data.world.insert(self.assets.3.clone());
+        data.world.insert(self.assets.4.clone());
+        // The enterprise is kept as it was before arriving, so a restart can arrive again.
+        let mut enterprise = self.enterprise.clone();
+        if let Some(level) = data
+            .world
+            .read_resource::<AssetStorage<Level>>()
+            .get(&self.level)
+        {
+            enterprise.arrive(&level.reference.name, &level.contracts);
+        }
+        data.world.insert(enterprise);
+        prepare_replay(data.world);
+        initialize_level(data.world, &self.level);
+        begin_replay(data.world);
+        data.world.exec(|mut creator: UiCreator<'_>| {
+            creator.create("ui/hud.ron", ());
+        });
+    }
+
+    fn handle_event(
+        &mut self,
+        data: StateData<'_, GameData<'_, '_>>,
+        event: StateEvent,
+    ) -> SimpleTrans {
+        match &event {
+            StateEvent::Window(event) if restart_requested(data.world, event) => {
+                Trans::Switch(Box::new(GameplayState {
+                    assets: self.assets.clone(),
+                    level: self.level.clone(),
+                    enterprise: self.enterprise.clone(),
+                }))
+            }
+            StateEvent::Window(event) => match *event {
+                Event::WindowEvent { ref event, .. } => match *event {
+                    WindowEvent::CloseRequested => Trans::Quit,
+                    WindowEvent::Resized(LogicalSize { width, height }) => {
+                        data.world.exec(|mut camera: WriteStorage<Camera>| {
+                            if let Some(camera) = (&mut camera).join().next() {
+                                *camera =
+                                    Camera::standard_2d(width as f32 / 2.0, height as f32 / 2.0);
+                            }
+                        });
+                        Trans::None
+                    }
+                    _ => Trans::None,
+                },
+                _ => Trans::None,
+            },
+            _ => Trans::None,
+        }
+    }
+
+    fn update(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+        // let (entities, names): (Entities<'_>, ReadStorage<'_, Named>) = data.world.system_data();
+        // if get_named_entity(&entities, &names, "player").is_none() {
+        //     return SimpleTrans::Switch(Box::new(MenuState {
+        //         assets: self.assets.clone(),
+        //         menu: "game_over.ron",
+        //     }));
+        // }
+        // if get_named_entity(&entities, &names, "pylon").is_none() {
+        //     return SimpleTrans::Switch(Box::new(MenuState {
+        //         assets: self.assets.clone(),
+        //         menu: "game_over.ron",
+        //     }));
+        // }
+        next_input(data.world);
+        let mut enterprise = { data.world.read_resource::<Enterprise>().deref().clone() };
+        let jumped = data.world.exec(|deliveries: ReadStorage<DeliveryZone>| {
+            (&deliveries)
+                .join()
+                .find(|delivery| delivery.jumped())
+                .is_some()
+        });
+        let out_of_time = {
+            let level = data.world.read_resource::<Level>();
+            data.world.read_resource::<LevelTimer>().expired(&level)
+        };
+        if jumped || out_of_time || enterprise.fuel() <= 0.0 {
+            finish_replay(data.world);
+            let (name, fuel_price, par_time) = {
+                let level = data.world.read_resource::<Level>();
+                (
+                    level.reference.name.clone(),
+                    level.fuel_price(),
+                    level.par_time,
+                )
+            };
+            if jumped {
+                let elapsed = data.world.read_resource::<LevelTimer>().elapsed;
+                let points = score(
+                    &enterprise.level_summary(),
+                    enterprise.fuel(),
+                    elapsed,
+                    par_time,
+                );
+                enterprise.record_score(&name, points);
+            }
+            // The tank isn't topped up after the tutorial.
+            if name != "Tutorial" {
+                enterprise.refuel(fuel_price);
+            }
+            if let Some(mut statistics) = data.world.try_fetch_mut::<Statistics>() {
+                statistics.finish_level(&name, &enterprise.level_summary());
+            }
+            let asteroids = data.world.read_resource::<Materials>().types();
+            enterprise
+                .market_mut()
+                .shake(&mut *data.world.write_resource::<GameRng>(), &asteroids);
+            if enterprise.bankrupt() {
+                enterprise.declare_bankruptcy();
+                return SimpleTrans::Switch(Box::new(MenuState::game_over(
+                    self.assets.clone(),
+                    enterprise,
+                )));
+            }
+            return SimpleTrans::Switch(Box::new(MenuState::end_level(
+                self.assets.clone(),
+                Some(enterprise),
+            )));
+        }
+        SimpleTrans::None
+    }
+}
+
+/// The asset processors and bundles that make up the simulation, with no window, audio or UI.
+pub fn with_gameplay_systems<'a, 'b>(
+    builder: GameDataBuilder<'a, 'b>,
+) -> amethyst::Result<GameDataBuilder<'a, 'b>> {
+    Ok(builder
+        .with(Processor::<Level>::new(), "level_loader", &[])
+        .with(Processor::<Chemistry>::new(), "chemistry_loader", &[])
+        .with(Processor::<Materials>::new(), "materials_loader", &[])
+        .with_bundle(TransformBundle::new())?
+        .with_bundle(PhysicsBundle::default())?
+        .with_bundle(AsteroidBundle)?
+        .with_bundle(StatsBundle)?
+        .with_bundle(AchievementBundle)?
+        .with_bundle(TutorialBundle)?
+        .with_bundle(ScriptingBundle)?
+        .with_bundle(ParticleBundle)?
+        .with_bundle(LevelBundle)?
+        .with_bundle(PlayerBundle)?)
+}
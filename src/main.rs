@@ -1,140 +1,29 @@
 #![allow(warnings)]
-#[macro_use]
-extern crate serde;
-use std::{
-    ops::Deref,
-    path::{Path, PathBuf},
-};
+use std::path::{Path, PathBuf};
 
 use amethyst::{
-    assets::{AssetStorage, Directory, Processor, ProgressCounter, Source},
-    audio::{output::init_output, AudioBundle},
-    core::{HideHierarchySystem, HideHierarchySystemDesc, Transform, TransformBundle},
-    ecs::*,
-    input::is_close_requested,
+    assets::Directory,
+    audio::AudioBundle,
+    core::HideHierarchySystemDesc,
     prelude::*,
     renderer::{
-        types::DefaultBackend, Camera, RenderDebugLines, RenderFlat2D, RenderToWindow,
-        RenderingBundle,
+        types::DefaultBackend, RenderDebugLines, RenderFlat2D, RenderToWindow, RenderingBundle,
     },
-    tiles::RenderTiles2D,
-    ui::{RenderUi, UiBundle, UiCreator, UiEventType, UiFinder},
+    ui::{RenderUi, UiBundle},
     utils::{application_root_dir, fps_counter::FpsCounterBundle},
-    winit::{dpi::LogicalSize, Event, WindowEvent},
-    Application, GameData, GameDataBuilder, LogLevelFilter, LoggerConfig, SimpleState, SimpleTrans,
-    StateData, StateEvent, StdoutLog, Trans,
-};
-use assets::{
-    load_level, load_sound_file, load_spritesheet, DjSystem, LevelStorage, LoadingState,
-    SoundStorage, SpriteStorage,
+    Application, GameDataBuilder, LogLevelFilter, LoggerConfig, StdoutLog,
 };
-use asteroid::{generate_asteroid, generate_asteroid_field, AsteroidBundle, AsteroidType};
-use billboards::BillboardBundle;
-use delivery::DeliveryZone;
-use economy::{EconomyBundle, Enterprise};
-use level::{generate_boundaries, initialize_level, Level, LevelBundle, LevelHandle};
-use particles::ParticleBundle;
-use physics::{PhysicsBundle, PhysicsHandle};
-use player::{initialize_player, PlayerBundle};
-use serde::Deserialize;
-
-use crate::{
-    delivery::generate_delivery_zone,
-    menu::{CardDesc, MenuState, MenuTransition},
+use ld49::{
+    assets::{DjSystem, LoadingState},
+    billboards::BillboardBundle,
+    dev,
+    editor::EditorBundle,
+    headless,
+    hud::HudBundle,
+    replay::{Replay, ReplayMode},
+    stats::StatsScreenSystem,
+    with_gameplay_systems,
 };
-mod assets;
-mod asteroid;
-mod billboards;
-mod delivery;
-mod economy;
-mod explosions;
-mod level;
-mod menu;
-mod particles;
-mod physics;
-mod player;
-mod tractor;
-
-type ASSETS = (SpriteStorage, LevelStorage, SoundStorage);
-
-struct GameplayState {
-    assets: ASSETS,
-    level: LevelHandle,
-    enterprise: Enterprise,
-}
-impl SimpleState for GameplayState {
-    fn on_start(&mut self, mut data: StateData<'_, GameData<'_, '_>>) {
-        data.world.delete_all();
-        data.world.insert(self.assets.0.clone());
-        data.world.insert(self.assets.1.clone());
-        data.world.insert(self.assets.2.clone());
-        data.world.insert(self.enterprise.clone());
-        initialize_level(data.world, &self.level);
-        data.world.exec(|mut creator: UiCreator<'_>| {
-            creator.create("ui/hud.ron", ());
-        });
-    }
-
-    fn handle_event(
-        &mut self,
-        data: StateData<'_, GameData<'_, '_>>,
-        event: StateEvent,
-    ) -> SimpleTrans {
-        match &event {
-            StateEvent::Window(event) => match *event {
-                Event::WindowEvent { ref event, .. } => match *event {
-                    WindowEvent::CloseRequested => Trans::Quit,
-                    WindowEvent::Resized(LogicalSize { width, height }) => {
-                        data.world.exec(|mut camera: WriteStorage<Camera>| {
-                            if let Some(camera) = (&mut camera).join().next() {
-                                *camera =
-                                    Camera::standard_2d(width as f32 / 2.0, height as f32 / 2.0);
-                            }
-                        });
-                        Trans::None
-                    }
-                    _ => Trans::None,
-                },
-                _ => Trans::None,
-            },
-            _ => Trans::None,
-        }
-    }
-
-    fn update(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
-        // let (entities, names): (Entities<'_>, ReadStorage<'_, Named>) = data.world.system_data();
-        // if get_named_entity(&entities, &names, "player").is_none() {
-        //     return SimpleTrans::Switch(Box::new(MenuState {
-        //         assets: self.assets.clone(),
-        //         menu: "game_over.ron",
-        //     }));
-        // }
-        // if get_named_entity(&entities, &names, "pylon").is_none() {
-        //     return SimpleTrans::Switch(Box::new(MenuState {
-        //         assets: self.assets.clone(),
-        //         menu: "game_over.ron",
-        //     }));
-        // }
-        let mut enterprise = { data.world.read_resource::<Enterprise>().deref().clone() };
-        if data.world.exec(|deliveries: ReadStorage<DeliveryZone>| {
-            (&deliveries)
-                .join()
-                .find(|delivery| delivery.jumped())
-                .is_some()
-        }) || enterprise.fuel <= 0.0
-        {
-            let mut level = { data.world.read_resource::<Level>().deref().clone() };
-            if level.reference.name != "Tutorial" {
-                enterprise.refuel();
-            }
-            return SimpleTrans::Switch(Box::new(MenuState::end_level(
-                self.assets.clone(),
-                Some(enterprise),
-            )));
-        }
-        SimpleTrans::None
-    }
-}
 
 fn main() -> amethyst::Result<()> {
     let mut logger_config = LoggerConfig::default();
@@ -150,10 +39,49 @@ fn main() -> amethyst::Result<()> {
 
     let assets_dir = app_root.join("assets/");
 
-    let game_data = GameDataBuilder::default()
-        .with(Processor::<Level>::new(), "level_loader", &[])
+    let args: Vec<String> = std::env::args().collect();
+    let dev = args.iter().any(|arg| arg == "--dev");
+    let seed = args
+        .iter()
+        .position(|arg| arg == "--seed")
+        .and_then(|idx| args.get(idx + 1))
+        .and_then(|seed| seed.parse().ok());
+    let replay = if let Some(idx) = args.iter().position(|arg| arg == "--replay") {
+        let path = args
+            .get(idx + 1)
+            .ok_or_else(|| amethyst::Error::from_string("--replay needs a file"))?;
+        ReplayMode::Playback(Replay::load(Path::new(path))?)
+    } else if let Some(idx) = args.iter().position(|arg| arg == "--record") {
+        let path = args
+            .get(idx + 1)
+            .ok_or_else(|| amethyst::Error::from_string("--record needs a file"))?;
+        ReplayMode::Record(PathBuf::from(path))
+    } else {
+        ReplayMode::Live
+    };
+    if let Some(idx) = args.iter().position(|arg| arg == "--headless") {
+        let mut values = args[idx + 1..]
+            .iter()
+            .take_while(|arg| !arg.starts_with("--"));
+        let level_path = values
+            .next()
+            .cloned()
+            .ok_or_else(|| amethyst::Error::from_string("--headless needs a level file"))?;
+        let frames = match values.next() {
+            Some(frames) => frames.parse().map_err(|_| {
+                amethyst::Error::from_string(format!(
+                    "--headless frames must be a number, not {}",
+                    frames
+                ))
+            })?,
+            None => 3600,
+        };
+        return headless::run_headless(level_path, frames, seed, replay);
+    }
+
+    let game_data = with_gameplay_systems(GameDataBuilder::default())?
         .with(DjSystem, "dj", &[])
-        .with_bundle(TransformBundle::new())?
+        .with(StatsScreenSystem, "stats_screen", &[])
         .with_system_desc(HideHierarchySystemDesc, "hide_hieracry", &[])
         .with_bundle(
             amethyst::input::InputBundle::<amethyst::input::StringBindings>::new()
@@ -170,19 +98,25 @@ fn main() -> amethyst::Result<()> {
                 .with_plugin(RenderUi::default()),
         )?
         .with_bundle(AudioBundle::default())?
-        .with_bundle(PhysicsBundle)?
-        .with_bundle(AsteroidBundle)?
-        .with_bundle(EconomyBundle)?
         .with_bundle(BillboardBundle)?
-        .with_bundle(ParticleBundle)?
-        .with_bundle(LevelBundle)?
-        .with_bundle(PlayerBundle)?
+        .with_bundle(HudBundle)?
         .with_bundle(FpsCounterBundle)?
         .with_bundle(UiBundle::<amethyst::input::StringBindings>::new())?;
+    let game_data = if dev {
+        game_data
+            .with_bundle(dev::hot_reload_bundle())?
+            .with_bundle(EditorBundle)?
+    } else {
+        game_data
+    };
 
     let mut game = Application::new(
         assets_dir,
-        LoadingState::with_levels(Directory::new("assets"), "levels/levels.ron")?,
+        LoadingState::with_levels(Directory::new("assets"), "levels/levels.ron")?
+            .with_achievements(Directory::new("assets"), "achievements.ron")?
+            .with_seed(seed)
+            .with_dev_mode(dev)
+            .with_replay(replay),
         game_data,
     )?;
     game.run();
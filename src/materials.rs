@@ -0,0 +1,61 @@
+use std::collections::HashMap;
+
+use amethyst::{
+    assets::{Asset, Handle},
+    ecs::*,
+};
+
+use crate::asteroid::AsteroidType;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct MaterialDesc {
+    pub sprite_number: usize,
+    pub radius: f32,
+    pub mass: f32,
+    pub base_ppm: f32,
+}
+
+impl Default for MaterialDesc {
+    fn default() -> Self {
+        MaterialDesc {
+            sprite_number: 3,
+            radius: 4.0,
+            mass: 5.0,
+            base_ppm: 1.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Materials {
+    materials: HashMap<AsteroidType, MaterialDesc>,
+}
+
+pub type MaterialsHandle = Handle<Materials>;
+
+impl Asset for Materials {
+    const NAME: &'static str = "ld49::Materials";
+    type Data = Materials;
+    type HandleStorage = VecStorage<MaterialsHandle>;
+}
+
+impl Materials {
+    /// Types missing from the catalogue fall back to a small, plain rock.
+    pub fn get(&self, asteroid_type: &AsteroidType) -> MaterialDesc {
+        self.materials
+            .get(asteroid_type)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Every type in the catalogue, in a stable order.
+    pub fn types(&self) -> Vec<AsteroidType> {
+        let mut types: Vec<AsteroidType> = self.materials.keys().cloned().collect();
+        types.sort_by_key(|asteroid_type| format!("{:?}", asteroid_type));
+        types
+    }
+
+    pub fn contains(&self, asteroid_type: &AsteroidType) -> bool {
+        self.materials.contains_key(asteroid_type)
+    }
+}
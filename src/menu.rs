@@ -1,8 +1,3 @@
-use std::{
-    fs::File,
-    io::{BufWriter, Write},
-};
-
 use amethyst::{
     assets::AssetStorage,
     core::{HiddenPropagate, Parent},
@@ -14,11 +9,22 @@ use amethyst::{
 };
 
 use crate::{
+    achievements::Unlocked,
+    dev::DevMode,
     economy::Enterprise,
+    editor::EditorState,
     level::{Level, LevelHandle},
+    materials::Materials,
+    procgen::{difficulty, generate_level, sector_seed, OfferedSector},
+    rng::StartupSeed,
+    save::{load_slot, set_aside, write_slot, SaveSlot, SAVE_SLOTS},
+    stats::Statistics,
     GameplayState, ASSETS,
 };
 
+/// Level cards that fit in the six card menu alongside the one to retire.
+const LEVEL_CARDS: usize = 5;
+
 #[derive(Serialize, Deserialize, Clone, Default)]
 pub struct CardDesc {
     title: String,
@@ -35,12 +41,21 @@ impl CardDesc {
             sprite_number: sprite_number,
         }
     }
+
+    pub fn sprite_number(&self) -> usize {
+        self.sprite_number
+    }
 }
 
 pub enum MenuTransition {
     Begin,
     Continue,
+    Repay,
     Level(Level, LevelHandle),
+    Slot(String),
+    Stats,
+    Editor,
+    Back,
     Quit,
 }
 
@@ -49,6 +64,7 @@ pub struct MenuState {
     menu: &'static str,
     enterprise: Option<Enterprise>,
     cards: Vec<(CardDesc, MenuTransition)>,
+    slot: Option<String>,
     initialized: bool,
 }
 
@@ -64,14 +80,70 @@ pub fn find_by_id<'s>(
 }
 
 impl MenuState {
+    /// The main menu. Dev mode adds the level editor to it.
+    pub fn slot_menu(assets: ASSETS) -> MenuState {
+        let mut cards = vec![];
+        for (idx, slot) in SAVE_SLOTS.iter().enumerate() {
+            let title = match load_slot(slot) {
+                Ok(Some(enterprise)) => {
+                    format!("Enterprise {}: {} funds", idx + 1, enterprise.funds())
+                }
+                Ok(None) => format!("Enterprise {}: Unclaimed", idx + 1),
+                Err(error) => {
+                    log::warn!("Could not load save {}: {}", slot, error);
+                    format!("Enterprise {}: Unreadable!", idx + 1)
+                }
+            };
+            cards.push((
+                CardDesc::new(title, 0),
+                MenuTransition::Slot(slot.to_string()),
+            ));
+        }
+        cards.push((
+            CardDesc::new("Retire For The Day...", 0),
+            MenuTransition::Quit,
+        ));
+        MenuState {
+            assets,
+            menu: "ui/six_menu.ron",
+            enterprise: None,
+            cards,
+            slot: None,
+            initialized: false,
+        }
+    }
     pub fn end_level(assets: ASSETS, enterprise: Option<Enterprise>) -> MenuState {
+        let mut cards = vec![(
+            CardDesc::new("Continue Your Enterprise!", 0),
+            MenuTransition::Continue,
+        )];
+        if enterprise.as_ref().map_or(false, |enterprise| {
+            enterprise.debt() > 0 && enterprise.funds() > 0
+        }) {
+            cards.push((CardDesc::new("Repay Your Loans!", 0), MenuTransition::Repay));
+        }
+        cards.push((
+            CardDesc::new("Retire For The Day...", 0),
+            MenuTransition::Quit,
+        ));
         MenuState {
             assets,
             menu: "ui/end_level_menu.ron",
             enterprise,
+            cards,
+            slot: None,
+            initialized: false,
+        }
+    }
+    /// Shown once an enterprise has gone bankrupt and been reset.
+    pub fn game_over(assets: ASSETS, enterprise: Enterprise) -> MenuState {
+        MenuState {
+            assets,
+            menu: "ui/game_over_menu.ron",
+            enterprise: Some(enterprise),
             cards: vec![
                 (
-                    CardDesc::new("Continue Your Enterprise!", 0),
+                    CardDesc::new("Restart Your Enterprise!", 0),
                     MenuTransition::Continue,
                 ),
                 (
@@ -79,6 +151,7 @@ impl MenuState {
                     MenuTransition::Quit,
                 ),
             ],
+            slot: None,
             initialized: false,
         }
     }
@@ -89,6 +162,7 @@ impl MenuState {
     ) -> MenuState {
         let menu = match cards.len() {
             3 => "ui/three_menu.ron",
+            4..=6 => "ui/six_menu.ron",
             _ => panic!(
                 "Failed to find a valid cards menu for {} cards",
                 cards.len()
@@ -99,19 +173,38 @@ impl MenuState {
             menu,
             enterprise,
             cards,
+            slot: None,
             initialized: false,
         }
     }
+    pub fn stats(assets: ASSETS) -> MenuState {
+        MenuState {
+            assets,
+            menu: "ui/stats_menu.ron",
+            enterprise: None,
+            cards: vec![(CardDesc::new("Back", 0), MenuTransition::Back)],
+            slot: None,
+            initialized: false,
+        }
+    }
+    pub fn with_slot(mut self, slot: String) -> Self {
+        self.slot = Some(slot);
+        self
+    }
+    /// The hand-made levels on offer, then the generated ones, cutting hand-made ones short so
+    /// every generated level and the way out still fit.
     pub fn level_menu(
         assets: ASSETS,
         mut levels: Vec<(Level, LevelHandle)>,
+        mut generated: Vec<(Level, LevelHandle)>,
         enterprise: Option<Enterprise>,
     ) -> MenuState {
         let mut cards = vec![];
-        if let Some(enterprise) = &enterprise {
-            levels = enterprise.get_next_levels(levels);
-        }
-        for (level, handle) in levels.iter() {
+        let offering = enterprise.clone().unwrap_or_default();
+        levels.retain(|(level, _)| offering.offers(&level.reference.name, &level.unlock));
+        generated.retain(|(level, _)| offering.offers(&level.reference.name, &level.unlock));
+        levels.truncate(LEVEL_CARDS.saturating_sub(generated.len()));
+        for (level, handle) in levels.iter().chain(generated.iter()) {
             cards.push((
                 level.card.clone(),
                 MenuTransition::Level(level.clone(), handle.clone()),
@@ -126,6 +219,7 @@ impl MenuState {
             menu: "ui/six_menu.ron",
             enterprise,
             cards,
+            slot: None,
             initialized: false,
         }
     }
@@ -134,13 +228,23 @@ impl MenuState {
 impl SimpleState for MenuState {
     fn on_start(&mut self, mut data: StateData<'_, GameData<'_, '_>>) {
         data.world.delete_all();
+        if let Some(slot) = &self.slot {
+            data.world.insert(SaveSlot(slot.clone()));
+            data.world.insert(Statistics::load(slot));
+            data.world.insert(Unlocked::load(slot));
+        }
         if let Some(enterprise) = &self.enterprise {
             data.world.insert(enterprise.clone());
-            if let Ok(mut file) = File::create("enterprise.ron") {
-                if let Ok(save) = ron::ser::to_string(enterprise) {
-                    file.write(save.as_bytes());
+            if let Some(slot) = data.world.try_fetch::<SaveSlot>() {
+                if let Err(error) = write_slot(&slot.0, enterprise) {
+                    log::error!("Could not save to {}: {}", slot.0, error);
+                }
+                if let Some(statistics) = data.world.try_fetch::<Statistics>() {
+                    statistics.save(&slot.0);
+                }
+                if let Some(unlocked) = data.world.try_fetch::<Unlocked>() {
+                    unlocked.save(&slot.0);
                 }
-            } else {
             }
         }
         data.world.exec(|mut creator: UiCreator<'_>| {
@@ -148,6 +252,15 @@ impl SimpleState for MenuState {
         });
     }
 
+    /// Menus pushed on top of this one cleared its cards away, so they're put back.
+    fn on_resume(&mut self, mut data: StateData<'_, GameData<'_, '_>>) {
+        data.world.delete_all();
+        data.world.exec(|mut creator: UiCreator<'_>| {
+            creator.create(self.menu, ());
+        });
+        self.initialized = false;
+    }
+
     fn update(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
         if !self.initialized {
             let menu = data.world.exec(|finder: UiFinder<'_>| finder.find("menu"));
@@ -196,67 +309,207 @@ impl SimpleState for MenuState {
                     Trans::None
                 }
             }
-            StateEvent::Ui(ui_event) => data.world.exec(
-                |(finder, level_storage): (UiFinder, Read<AssetStorage<Level>>)| {
-                    if ui_event.event_type == UiEventType::Click {
-                        for i in 0.. {
-                            if let (Some(card_entity), Some(card)) = (
-                                finder.find(format!("card_container_{}", i).as_ref()),
-                                self.cards.get(i),
-                            ) {
-                                if card_entity == ui_event.target {
-                                    match &card.1 {
-                                        MenuTransition::Begin => {
-                                            return Trans::Push(Box::new(GameplayState {
-                                                assets: self.assets.clone(),
-                                                enterprise: Enterprise::begin_enterprise(),
-                                                level: self.assets.1.levels.get(0).unwrap().clone(),
-                                            }));
-                                        }
-                                        MenuTransition::Continue => {
-                                            let mut levels = Vec::new();
-                                            for handle in self.assets.1.levels.iter() {
-                                                if let Some(level) = level_storage.get(&handle) {
-                                                    levels.push((level.clone(), handle.clone()));
+            StateEvent::Ui(ui_event) => {
+                data.world.exec(
+                    |(
+                        finder,
+                        mut level_storage,
+                        material_storage,
+                        startup_seed,
+                        mut offered,
+                        dev,
+                    ): (
+                        UiFinder,
+                        Write<AssetStorage<Level>>,
+                        Read<AssetStorage<Materials>>,
+                        Read<StartupSeed>,
+                        Write<OfferedSector>,
+                        Read<DevMode>,
+                    )| {
+                        if ui_event.event_type == UiEventType::Click {
+                            for i in 0.. {
+                                if let (Some(card_entity), Some(card)) = (
+                                    finder.find(format!("card_container_{}", i).as_ref()),
+                                    self.cards.get(i),
+                                ) {
+                                    if card_entity == ui_event.target {
+                                        match &card.1 {
+                                            MenuTransition::Begin => {
+                                                return Trans::Push(Box::new(GameplayState {
+                                                    assets: self.assets.clone(),
+                                                    enterprise: Enterprise::begin_enterprise(),
+                                                    level: self
+                                                        .assets
+                                                        .1
+                                                        .levels
+                                                        .get(0)
+                                                        .unwrap()
+                                                        .clone(),
+                                                }));
+                                            }
+                                            MenuTransition::Continue => {
+                                                let mut levels = Vec::new();
+                                                for handle in self.assets.1.levels.iter() {
+                                                    if let Some(level) = level_storage.get(&handle)
+                                                    {
+                                                        levels
+                                                            .push((level.clone(), handle.clone()));
+                                                    }
                                                 }
+                                                let mut generated = Vec::new();
+                                                if let Some(materials) =
+                                                    material_storage.get(&self.assets.4.materials)
+                                                {
+                                                    let enterprise = self.enterprise.as_ref();
+                                                    let completed = enterprise
+                                                        .map_or(0, Enterprise::levels_completed);
+                                                    let seed = sector_seed(
+                                                        startup_seed.0.unwrap_or_else(|| {
+                                                            enterprise.map_or(0, Enterprise::seed)
+                                                        }),
+                                                        completed,
+                                                    );
+                                                    match &offered.0 {
+                                                        Some((offered_seed, level, handle))
+                                                            if *offered_seed == seed =>
+                                                        {
+                                                            generated.push((
+                                                                level.clone(),
+                                                                handle.clone(),
+                                                            ));
+                                                        }
+                                                        _ => {
+                                                            let level = generate_level(
+                                                                seed,
+                                                                difficulty(completed),
+                                                                materials,
+                                                            );
+                                                            let handle =
+                                                                level_storage.insert(level.clone());
+                                                            offered.0 = Some((
+                                                                seed,
+                                                                level.clone(),
+                                                                handle.clone(),
+                                                            ));
+                                                            generated.push((level, handle));
+                                                        }
+                                                    }
+                                                }
+                                                return Trans::Push(Box::new(
+                                                    MenuState::level_menu(
+                                                        self.assets.clone(),
+                                                        levels,
+                                                        generated,
+                                                        self.enterprise.clone(),
+                                                    ),
+                                                ));
+                                            }
+                                            MenuTransition::Repay => {
+                                                let mut enterprise =
+                                                    self.enterprise.clone().unwrap_or_default();
+                                                enterprise.repay();
+                                                return Trans::Switch(Box::new(
+                                                    MenuState::end_level(
+                                                        self.assets.clone(),
+                                                        Some(enterprise),
+                                                    ),
+                                                ));
+                                            }
+                                            MenuTransition::Level(level, handle) => {
+                                                return Trans::Push(Box::new(GameplayState {
+                                                    assets: self.assets.clone(),
+                                                    enterprise: self
+                                                        .enterprise
+                                                        .clone()
+                                                        .unwrap_or(Enterprise::begin_enterprise()),
+                                                    level: handle.clone(),
+                                                }));
+                                            }
+                                            MenuTransition::Slot(slot) => {
+                                                let enterprise = match load_slot(slot) {
+                                                    Ok(enterprise) => enterprise,
+                                                    Err(error) => {
+                                                        log::warn!(
+                                                            "Starting over in {}: {}",
+                                                            slot,
+                                                            error
+                                                        );
+                                                        if let Err(error) = set_aside(slot) {
+                                                            log::error!(
+                                                                "Could not set aside {}: {}",
+                                                                slot,
+                                                                error
+                                                            );
+                                                        }
+                                                        None
+                                                    }
+                                                };
+                                                let mut cards = vec![
+                                                    (
+                                                        CardDesc::new("Begin Your Enterprise!", 0),
+                                                        MenuTransition::Begin,
+                                                    ),
+                                                    (
+                                                        CardDesc::new(
+                                                            "Continue Your Enterprise!",
+                                                            0,
+                                                        ),
+                                                        MenuTransition::Continue,
+                                                    ),
+                                                    (
+                                                        CardDesc::new("Statistics", 0),
+                                                        MenuTransition::Stats,
+                                                    ),
+                                                ];
+                                                if dev.0 {
+                                                    cards.push((
+                                                        CardDesc::new("Level Editor", 0),
+                                                        MenuTransition::Editor,
+                                                    ));
+                                                }
+                                                cards.push((
+                                                    CardDesc::new("Retire For The Day...", 0),
+                                                    MenuTransition::Quit,
+                                                ));
+                                                return Trans::Push(Box::new(
+                                                    MenuState::card_menu(
+                                                        self.assets.clone(),
+                                                        cards,
+                                                        enterprise,
+                                                    )
+                                                    .with_slot(slot.clone()),
+                                                ));
+                                            }
+                                            MenuTransition::Stats => {
+                                                return Trans::Push(Box::new(MenuState::stats(
+                                                    self.assets.clone(),
+                                                )));
+                                            }
+                                            MenuTransition::Editor => {
+                                                return Trans::Push(Box::new(EditorState::new(
+                                                    self.assets.clone(),
+                                                )));
+                                            }
+                                            MenuTransition::Back => {
+                                                return Trans::Pop;
+                                            }
+                                            MenuTransition::Quit => {
+                                                return Trans::Quit;
+                                            }
+                                            _ => {
+                                                return Trans::None;
                                             }
-                                            levels = self
-                                                .enterprise
-                                                .as_ref()
-                                                .unwrap_or(&Enterprise::begin_enterprise())
-                                                .get_next_levels(levels);
-                                            return Trans::Push(Box::new(MenuState::level_menu(
-                                                self.assets.clone(),
-                                                levels,
-                                                self.enterprise.clone(),
-                                            )));
-                                        }
-                                        MenuTransition::Level(level, handle) => {
-                                            return Trans::Push(Box::new(GameplayState {
-                                                assets: self.assets.clone(),
-                                                enterprise: self
-                                                    .enterprise
-                                                    .clone()
-                                                    .unwrap_or(Enterprise::begin_enterprise()),
-                                                level: handle.clone(),
-                                            }));
-                                        }
-                                        MenuTransition::Quit => {
-                                            return Trans::Quit;
-                                        }
-                                        _ => {
-                                            return Trans::None;
                                         }
                                     }
+                                } else {
+                                    break;
                                 }
-                            } else {
-                                break;
                             }
                         }
-                    }
-                    Trans::None
-                },
-            ),
+                        Trans::None
+                    },
+                )
+            }
             _ => Trans::None,
         }
     }
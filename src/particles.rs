@@ -8,13 +8,14 @@ use amethyst::{
     Error,
 };
 use nalgebra::{Point2, Vector2};
+use rand::Rng;
 
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub enum ParticleType {
     TractorHeavy,
     TractorLight,
     TractorPull,
-    Delivery,
+    Delivery(usize),
     Player,
     Explosion(usize),
 }
@@ -39,12 +40,12 @@ impl Particle {
             rotation: f32::atan2(direction.y, direction.x),
         }
     }
-    pub fn tractor_light(direction: Vector2<f32>) -> Self {
+    pub fn tractor_light(direction: Vector2<f32>, rng: &mut impl Rng) -> Self {
         Particle {
             my_type: ParticleType::TractorLight,
             lifetime: 0.2,
             velocity: (50.0, 0.0),
-            delta_velocity: (0.0, 5.0 - rand::random::<f32>() * 10.0),
+            delta_velocity: (0.0, 5.0 - rng.gen::<f32>() * 10.0),
             rotation: f32::atan2(direction.y, direction.x),
         }
     }
@@ -57,34 +58,51 @@ impl Particle {
             rotation: f32::atan2(direction.y, direction.x),
         }
     }
-    pub fn tractor_pull(direction: Vector2<f32>) -> Self {
+    pub fn tractor_pull(direction: Vector2<f32>, rng: &mut impl Rng) -> Self {
         Particle {
             my_type: ParticleType::TractorPull,
             lifetime: 0.1,
             velocity: (75.0, 0.0),
-            delta_velocity: (15.0, 15.0 - rand::random::<f32>() * 30.0),
+            delta_velocity: (15.0, 15.0 - rng.gen::<f32>() * 30.0),
             rotation: f32::atan2(direction.y, direction.x),
         }
     }
-    pub fn delivery(direction: Vector2<f32>) -> Self {
+    pub fn delivery(direction: Vector2<f32>, rng: &mut impl Rng) -> Self {
+        let sprite_number = if rng.gen() {
+            if rng.gen() {
+                33
+            } else {
+                34
+            }
+        } else {
+            if rng.gen() {
+                35
+            } else {
+                36
+            }
+        };
         Particle {
-            my_type: ParticleType::Delivery,
+            my_type: ParticleType::Delivery(sprite_number),
             lifetime: 0.3,
-            velocity: (rand::random::<f32>() * 30.0 + 30.0, 0.0),
-            delta_velocity: (rand::random::<f32>() * 30.0, 0.0),
+            velocity: (rng.gen::<f32>() * 30.0 + 30.0, 0.0),
+            delta_velocity: (rng.gen::<f32>() * 30.0, 0.0),
             rotation: f32::atan2(direction.y, direction.x),
         }
     }
-    pub fn explosion(sprite_numbers: &Vec<usize>, direction: Vector2<f32>) -> Self {
+    pub fn explosion(
+        sprite_numbers: &Vec<usize>,
+        direction: Vector2<f32>,
+        rng: &mut impl Rng,
+    ) -> Self {
         Particle {
             my_type: ParticleType::Explosion(
                 *sprite_numbers
-                    .get((rand::random::<f32>() * sprite_numbers.len() as f32) as usize)
+                    .get((rng.gen::<f32>() * sprite_numbers.len() as f32) as usize)
                     .unwrap(),
             ),
-            lifetime: rand::random::<f32>() + 0.2,
-            velocity: (rand::random::<f32>() * 100.0 + 100.0, 0.0),
-            delta_velocity: (rand::random::<f32>() * -100.0, 0.0),
+            lifetime: rng.gen::<f32>() + 0.2,
+            velocity: (rng.gen::<f32>() * 100.0 + 100.0, 0.0),
+            delta_velocity: (rng.gen::<f32>() * -100.0, 0.0),
             rotation: f32::atan2(direction.y, direction.x),
         }
     }
@@ -95,27 +113,13 @@ impl Particle {
             ParticleType::TractorLight => 13,
             ParticleType::TractorPull => 14,
             ParticleType::Player => 48,
-            ParticleType::Delivery => {
-                if rand::random() {
-                    if rand::random() {
-                        33
-                    } else {
-                        34
-                    }
-                } else {
-                    if rand::random() {
-                        35
-                    } else {
-                        36
-                    }
-                }
-            }
+            ParticleType::Delivery(sprite_number) => sprite_number,
         }
     }
 }
 
-pub fn random_direction() -> Vector2<f32> {
-    let rotation = rand::random::<f32>() * PI * 2.0;
+pub fn random_direction(rng: &mut impl Rng) -> Vector2<f32> {
+    let rotation = rng.gen::<f32>() * PI * 2.0;
     Vector2::new(f32::cos(rotation), f32::sin(rotation))
 }
 
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use amethyst::core::bundle::SystemBundle;
 use amethyst::core::Time;
 use amethyst::ecs::*;
@@ -103,6 +105,8 @@ pub struct Physics {
     pub colliders: DefaultColliderSet<N>,
     pub joint_constraints: DefaultJointConstraintSet<N>,
     pub force_generators: DefaultForceGeneratorSet<N>,
+    /// Forces that last for a whole frame, applied again on each step taken for it.
+    held: Vec<(DefaultBodyHandle, Force<N>, ForceType)>,
 }
 
 impl Physics {
@@ -121,6 +125,11 @@ impl Physics {
     }
 
     pub fn step_with_timestep(&mut self, timestep: N) {
+        for (handle, force, force_type) in self.held.iter() {
+            if let Some(body) = self.bodies.get_mut(*handle) {
+                body.apply_force(0, force, *force_type, true);
+            }
+        }
         self.mech_world.set_timestep(timestep);
         self.mech_world.step(
             &mut self.geo_world,
@@ -267,41 +276,38 @@ impl Physics {
         }
     }
 
+    /// Holds a force on a body until the next frame's steps are done, so it acts on every one.
+    fn hold(&mut self, handle: &PhysicsHandle, force: Force<N>, force_type: ForceType) {
+        if let Some(handle) = handle.body {
+            self.held.push((handle, force, force_type));
+        }
+    }
+
+    /// Drops the forces held for the frame just stepped.
+    pub fn release_held(&mut self) {
+        self.held.clear();
+    }
+
     pub fn apply_dampening(&mut self, handle: &PhysicsHandle, mag: N) {
         if let Some(current_vel) = self.get_velocity(handle) {
-            if let Some(handle) = handle.body {
-                if let Some(body) = self.bodies.get_mut(handle) {
-                    body.apply_force(
-                        0,
-                        &Force::linear(-current_vel * mag / (1.0 + mag)),
-                        ForceType::AccelerationChange,
-                        true,
-                    );
-                }
-            }
+            self.hold(
+                handle,
+                Force::linear(-current_vel * mag / (1.0 + mag)),
+                ForceType::AccelerationChange,
+            );
         }
     }
 
+    /// A change in velocity on every step of the frame, for a steady pull.
     pub fn apply_velocity_change(&mut self, handle: &PhysicsHandle, vec: Vector2<N>) {
-        if let Some(handle) = handle.body {
-            if let Some(body) = self.bodies.get_mut(handle) {
-                body.apply_force(0, &Force::linear(vec), ForceType::VelocityChange, true)
-            } else {
-                panic!("HOLY SHIT");
-            }
-        } else {
-            panic!("NOLY SHIT");
-        }
+        self.hold(handle, Force::linear(vec), ForceType::VelocityChange);
     }
 
     pub fn apply_force(&mut self, handle: &PhysicsHandle, vec: Vector2<N>) {
-        if let Some(handle) = handle.body {
-            if let Some(body) = self.bodies.get_mut(handle) {
-                body.apply_force(0, &Force::linear(vec), ForceType::Force, true);
-            }
-        }
+        self.hold(handle, Force::linear(vec), ForceType::Force);
     }
 
+    /// A one-off kick, applied straight away rather than on each step.
     pub fn apply_impulse(&mut self, handle: &PhysicsHandle, vec: Vector2<N>) {
         if let Some(handle) = handle.body {
             if let Some(body) = self.bodies.get_mut(handle) {
@@ -416,15 +422,60 @@ impl Default for Physics {
             colliders: DefaultColliderSet::new(),
             joint_constraints: DefaultJointConstraintSet::new(),
             force_generators: DefaultForceGeneratorSet::new(),
+            held: Vec::new(),
         }
     }
 }
 
+/// Steps the simulation at a fixed rate, however long the frames take.
+pub struct PhysicsClock {
+    timestep: N,
+    accumulator: N,
+    /// Frame time beyond this many steps is dropped rather than caught up on.
+    max_steps: usize,
+    /// Body positions before the latest step, for drawing between steps.
+    previous: HashMap<DefaultBodyHandle, Isometry2<N>>,
+    /// Seconds simulated by the latest frame's steps.
+    stepped: N,
+}
+
+impl PhysicsClock {
+    pub fn new(steps_per_second: N) -> Self {
+        PhysicsClock {
+            timestep: 1.0 / steps_per_second,
+            accumulator: 0.0,
+            max_steps: 4,
+            previous: HashMap::new(),
+            stepped: 0.0,
+        }
+    }
+
+    /// Seconds simulated by the latest frame's steps, which is what anything charged by the
+    /// second, like fuel, should be charged for.
+    pub fn stepped(&self) -> N {
+        self.stepped
+    }
+
+    /// Forgets time and positions left over from the previous level.
+    pub fn reset(&mut self) {
+        self.accumulator = 0.0;
+        self.stepped = 0.0;
+        self.previous.clear();
+    }
+}
+
+impl Default for PhysicsClock {
+    fn default() -> Self {
+        Self::new(60.0)
+    }
+}
+
 struct PhysicsSystem;
 
 impl<'s> System<'s> for PhysicsSystem {
     type SystemData = (
         Write<'s, Physics>,
+        Write<'s, PhysicsClock>,
         ReadStorage<'s, PhysicsHandle>,
         WriteStorage<'s, Transform>,
         Write<'s, EventChannel<PhysicsContactEvent>>,
@@ -434,21 +485,41 @@ impl<'s> System<'s> for PhysicsSystem {
 
     fn run(
         &mut self,
-        (mut physics, handles, mut transforms, mut c_events, mut p_events, time): Self::SystemData,
+        (mut physics, mut clock, handles, mut transforms, mut c_events, mut p_events, time): Self::SystemData,
     ) {
-        physics.step_with_timestep(time.delta_seconds().clamp(1.0 / 60.0, 1.0 / 15.0));
+        clock.accumulator += time.delta_seconds();
+        let steps = ((clock.accumulator / clock.timestep) as usize).min(clock.max_steps);
+        for step in 0..steps {
+            if step + 1 == steps {
+                let positions = physics
+                    .bodies
+                    .iter()
+                    .filter_map(|(handle, body)| {
+                        body.downcast_ref::<RigidBody<N>>()
+                            .map(|rigid_body| (handle, *rigid_body.position()))
+                    })
+                    .collect();
+                clock.previous = positions;
+            }
+            physics.step_with_timestep(clock.timestep);
+            c_events.iter_write(physics.geo_world.contact_events().iter().cloned());
+            p_events.iter_write(physics.geo_world.proximity_events().iter().cloned());
+        }
+        physics.release_held();
+        clock.stepped = steps as N * clock.timestep;
+        clock.accumulator = (clock.accumulator - steps as N * clock.timestep).min(clock.timestep);
+        let alpha = clock.accumulator / clock.timestep;
         for (handle, transform) in (&handles, &mut transforms).join() {
-            if let Some(position) = physics.get_position(handle) {
-                let x = position.translation.x;
-                let y = position.translation.y;
-                let rotation_2d = position.rotation.angle();
-                transform.set_translation_x(x as f32);
-                transform.set_translation_y(y as f32);
-                transform.set_rotation_2d(rotation_2d as f32);
+            if let (Some(position), Some(body)) = (physics.get_position(handle), handle.body) {
+                let position = match clock.previous.get(&body) {
+                    Some(previous) => previous.lerp_slerp(&position, alpha),
+                    None => position,
+                };
+                transform.set_translation_x(position.translation.x);
+                transform.set_translation_y(position.translation.y);
+                transform.set_rotation_2d(position.rotation.angle());
             }
         }
-        c_events.iter_write(physics.geo_world.contact_events().iter().cloned());
-        p_events.iter_write(physics.geo_world.proximity_events().iter().cloned());
     }
 }
 
@@ -551,17 +622,74 @@ impl<'s> System<'s> for PhysicsDeletionSystem {
     }
 }
 
-pub struct PhysicsBundle;
+pub struct PhysicsBundle {
+    pub steps_per_second: N,
+}
+
+impl Default for PhysicsBundle {
+    fn default() -> Self {
+        PhysicsBundle {
+            steps_per_second: 60.0,
+        }
+    }
+}
 
 impl<'a, 'b> SystemBundle<'a, 'b> for PhysicsBundle {
     fn build(
         self,
-        _world: &mut World,
+        world: &mut World,
         dispatcher: &mut DispatcherBuilder<'a, 'b>,
     ) -> Result<(), Error> {
+        world.insert(PhysicsClock::new(self.steps_per_second));
         dispatcher.add(PhysicsSpawningSystem, "physics_spawn", &[]);
         dispatcher.add(PhysicsSystem, "physics", &["physics_spawn"]);
         dispatcher.add(PhysicsDeletionSystem, "physics_delete", &[]);
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        asteroid::{Asteroid, AsteroidType},
+        test_support::{TestWorldBuilder, TICK},
+    };
+
+    /// The speed a held force gives a resting asteroid over one frame of `ticks` ticks.
+    fn pushed_speed(ticks: f32) -> N {
+        let mut test = TestWorldBuilder::new().build();
+        test.world.register::<Asteroid>();
+        test.spawn_asteroid(AsteroidType::Big, (0.0, 0.0));
+        test.run(1);
+        let handle = {
+            let handles = test.world.read_storage::<PhysicsHandle>();
+            (&handles).join().next().unwrap().clone()
+        };
+        test.world
+            .write_resource::<Physics>()
+            .apply_force(&handle, Vector2::new(1000.0, 0.0));
+        test.world
+            .write_resource::<Time>()
+            .set_delta_seconds(ticks * TICK);
+        test.run(1);
+        assert_eq!(
+            test.world.read_resource::<PhysicsClock>().stepped(),
+            ticks.floor() * TICK
+        );
+        let velocity = test
+            .world
+            .write_resource::<Physics>()
+            .get_velocity(&handle)
+            .unwrap();
+        velocity.x
+    }
+
+    #[test]
+    fn held_forces_act_on_every_step_of_a_frame() {
+        let once = pushed_speed(1.5);
+        let twice = pushed_speed(2.5);
+        assert!(once > 0.0);
+        assert!((twice / once - 2.0).abs() < 0.05, "{} {}", once, twice);
+    }
+}
@@ -0,0 +1,249 @@
+//! Where randomly placed asteroids go: the areas they're scattered over, and the bookkeeping that
+//! keeps them from spawning on top of each other, the player or a delivery zone.
+
+use rand::Rng;
+
+/// How many random spots are tried for an asteroid before it's left out.
+const ATTEMPTS: usize = 32;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub enum Area {
+    /// A rectangle by its centre and size.
+    Centered {
+        center: (f32, f32),
+        size: (f32, f32),
+    },
+    /// A rectangle by its bottom left corner and size.
+    Cornered {
+        corner: (f32, f32),
+        size: (f32, f32),
+    },
+    Circle {
+        center: (f32, f32),
+        radius: f32,
+    },
+    /// A polygon by its vertices, in order around either way.
+    Polygon(Vec<(f32, f32)>),
+}
+
+impl Area {
+    /// The smallest and largest corners of a box around the area.
+    pub fn bounds(&self) -> ((f32, f32), (f32, f32)) {
+        match self {
+            Area::Centered { center, size } => (
+                (center.0 - size.0 / 2.0, center.1 - size.1 / 2.0),
+                (center.0 + size.0 / 2.0, center.1 + size.1 / 2.0),
+            ),
+            Area::Cornered { corner, size } => (*corner, (corner.0 + size.0, corner.1 + size.1)),
+            Area::Circle { center, radius } => (
+                (center.0 - radius, center.1 - radius),
+                (center.0 + radius, center.1 + radius),
+            ),
+            Area::Polygon(vertices) => vertices.iter().fold(
+                ((f32::MAX, f32::MAX), (f32::MIN, f32::MIN)),
+                |((min_x, min_y), (max_x, max_y)), (x, y)| {
+                    (
+                        (min_x.min(*x), min_y.min(*y)),
+                        (max_x.max(*x), max_y.max(*y)),
+                    )
+                },
+            ),
+        }
+    }
+
+    pub fn center(&self) -> (f32, f32) {
+        match self {
+            Area::Centered { center, .. } | Area::Circle { center, .. } => *center,
+            _ => {
+                let ((min_x, min_y), (max_x, max_y)) = self.bounds();
+                ((min_x + max_x) / 2.0, (min_y + max_y) / 2.0)
+            }
+        }
+    }
+
+    /// Slides the whole area so its centre is at `point`.
+    pub fn move_to(&mut self, point: (f32, f32)) {
+        let (x, y) = self.center();
+        let (dx, dy) = (point.0 - x, point.1 - y);
+        match self {
+            Area::Centered { center, .. } | Area::Circle { center, .. } => *center = point,
+            Area::Cornered { corner, .. } => *corner = (corner.0 + dx, corner.1 + dy),
+            Area::Polygon(vertices) => {
+                for vertex in vertices.iter_mut() {
+                    *vertex = (vertex.0 + dx, vertex.1 + dy);
+                }
+            }
+        }
+    }
+
+    pub fn area(&self) -> f32 {
+        match self {
+            Area::Centered { size, .. } | Area::Cornered { size, .. } => (size.0 * size.1).abs(),
+            Area::Circle { radius, .. } => std::f32::consts::PI * radius * radius,
+            Area::Polygon(vertices) => {
+                let doubled: f32 = vertices
+                    .iter()
+                    .zip(vertices.iter().cycle().skip(1))
+                    .map(|((x1, y1), (x2, y2))| x1 * y2 - x2 * y1)
+                    .sum();
+                doubled.abs() / 2.0
+            }
+        }
+    }
+
+    pub fn contains(&self, (x, y): (f32, f32)) -> bool {
+        match self {
+            Area::Circle { center, radius } => (x - center.0).hypot(y - center.1) <= *radius,
+            Area::Polygon(vertices) => {
+                // Counts the edges a ray heading right from the point crosses.
+                let mut inside = false;
+                for ((x1, y1), (x2, y2)) in vertices.iter().zip(vertices.iter().cycle().skip(1)) {
+                    if (*y1 > y) != (*y2 > y) && x < x1 + (y - y1) / (y2 - y1) * (x2 - x1) {
+                        inside = !inside;
+                    }
+                }
+                inside
+            }
+            _ => {
+                let ((min_x, min_y), (max_x, max_y)) = self.bounds();
+                x >= min_x && x <= max_x && y >= min_y && y <= max_y
+            }
+        }
+    }
+
+    /// A point spread evenly over the area. Polygons are sampled from their bounding box, so a
+    /// very thin one may not give a point at all.
+    pub fn sample(&self, rng: &mut impl Rng) -> Option<(f32, f32)> {
+        match self {
+            Area::Circle { center, radius } => {
+                let distance = radius * rng.gen::<f32>().sqrt();
+                let angle = rng.gen::<f32>() * std::f32::consts::PI * 2.0;
+                Some((
+                    center.0 + distance * angle.cos(),
+                    center.1 + distance * angle.sin(),
+                ))
+            }
+            Area::Polygon(vertices) if vertices.len() < 3 => None,
+            _ => {
+                let ((min_x, min_y), (max_x, max_y)) = self.bounds();
+                (0..ATTEMPTS)
+                    .map(|_| {
+                        (
+                            min_x + rng.gen::<f32>() * (max_x - min_x),
+                            min_y + rng.gen::<f32>() * (max_y - min_y),
+                        )
+                    })
+                    .find(|point| self.contains(*point))
+            }
+        }
+    }
+}
+
+/// Everything placed in a level so far, and the spots kept clear.
+#[derive(Debug, Default)]
+pub struct Placement {
+    /// Extra room left between neighbouring asteroids, on top of their radii.
+    pub spacing: f32,
+    keep_out: Vec<((f32, f32), f32)>,
+    occupied: Vec<((f32, f32), f32)>,
+}
+
+impl Placement {
+    pub fn new(spacing: f32) -> Self {
+        Placement {
+            spacing,
+            ..Default::default()
+        }
+    }
+
+    /// Keeps random placements at least `radius` away from a point.
+    pub fn keep_out(&mut self, point: (f32, f32), radius: f32) {
+        self.keep_out.push((point, radius));
+    }
+
+    /// Records something placed exactly, so random placements avoid it.
+    pub fn occupy(&mut self, point: (f32, f32), radius: f32) {
+        self.occupied.push((point, radius));
+    }
+
+    pub fn is_clear(&self, (x, y): (f32, f32), radius: f32) -> bool {
+        let distance = |(px, py): (f32, f32)| (x - px).hypot(y - py);
+        self.keep_out
+            .iter()
+            .all(|(point, keep_out)| distance(*point) >= keep_out + radius)
+            && self
+                .occupied
+                .iter()
+                .all(|(point, other)| distance(*point) >= other + radius + self.spacing)
+    }
+
+    /// Finds a clear spot for something of `radius` from the spots `sample` suggests and takes
+    /// it. Gives up after a few tries, when the area is too crowded.
+    pub fn place<R: Rng>(
+        &mut self,
+        rng: &mut R,
+        radius: f32,
+        mut sample: impl FnMut(&mut R) -> Option<(f32, f32)>,
+    ) -> Option<(f32, f32)> {
+        let point = (0..ATTEMPTS)
+            .filter_map(|_| sample(rng))
+            .find(|point| self.is_clear(*point, radius))?;
+        self.occupy(point, radius);
+        Some(point)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::GameRng;
+
+    #[test]
+    fn areas_contain_what_they_sample() {
+        let mut rng = GameRng::new(Some(0));
+        let areas = [
+            Area::Centered {
+                center: (100.0, 0.0),
+                size: (50.0, 20.0),
+            },
+            Area::Cornered {
+                corner: (0.0, 0.0),
+                size: (10.0, 10.0),
+            },
+            Area::Circle {
+                center: (-50.0, -50.0),
+                radius: 30.0,
+            },
+            Area::Polygon(vec![(0.0, 0.0), (100.0, 0.0), (0.0, 100.0)]),
+        ];
+        for area in areas.iter() {
+            for _ in 0..100 {
+                assert!(area.contains(area.sample(&mut rng).unwrap()), "{:?}", area);
+            }
+        }
+        assert_eq!(areas[3].area(), 5000.0);
+        assert!(!areas[3].contains((80.0, 80.0)));
+        assert_eq!(areas[1].center(), (5.0, 5.0));
+    }
+
+    #[test]
+    fn placements_keep_their_distance() {
+        let mut rng = GameRng::new(Some(0));
+        let area = Area::Circle {
+            center: (0.0, 0.0),
+            radius: 100.0,
+        };
+        let mut placement = Placement::new(2.0);
+        placement.keep_out((0.0, 0.0), 40.0);
+        let points: Vec<_> = (0..40)
+            .filter_map(|_| placement.place(&mut rng, 4.0, |rng| area.sample(rng)))
+            .collect();
+        assert!(points.len() > 30);
+        for (index, (x, y)) in points.iter().enumerate() {
+            assert!(x.hypot(*y) >= 44.0);
+            for (ox, oy) in points[index + 1..].iter() {
+                assert!((x - ox).hypot(y - oy) >= 10.0);
+            }
+        }
+    }
+}
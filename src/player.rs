@@ -1,11 +1,11 @@
 use amethyst::{
     core::{Parent, SystemBundle, Time, Transform},
     ecs::*,
-    input::{InputHandler, StringBindings},
     prelude::*,
     renderer::{Camera, Sprite, SpriteRender},
     shred::Fetch,
     shred::World,
+    shrev::{EventChannel, ReaderId},
     utils::fps_counter::FpsCounter,
     window::ScreenDimensions,
     Error,
@@ -13,13 +13,19 @@ use amethyst::{
 use nalgebra::{Point2, Vector2};
 use ncollide2d::shape::{Ball, Cuboid, ShapeHandle};
 use nphysics2d::object::{BodyStatus, ColliderDesc, RigidBodyDesc};
+use rand::Rng;
 
 use crate::{
     assets::{SpriteHandles, SpriteRes, SpriteStorage},
-    delivery::{PlayerDeliveryArrowSystem, PlayerDeliverySystem, PlayerJumpSystem},
+    delivery::{
+        MarketRecoverySystem, PlayerDeliveryArrowSystem, PlayerDeliverySystem, PlayerJumpSystem,
+    },
     economy::Enterprise,
+    events::FuelDepleted,
     particles::{emit_particle, Particle},
-    physics::{Physics, PhysicsDesc, PhysicsHandle},
+    physics::{Physics, PhysicsClock, PhysicsDesc, PhysicsHandle},
+    replay::PlayerInput,
+    rng::GameRng,
     tractor::{PlayerTractorSystem, TractorGravitySystem},
 };
 
@@ -83,17 +89,18 @@ pub fn initialize_player(world: &mut World, transform: Transform) {
 struct PlayerMovementSystem;
 impl<'s> System<'s> for PlayerMovementSystem {
     type SystemData = (
-        Read<'s, InputHandler<StringBindings>>,
+        Read<'s, PlayerInput>,
         Write<'s, Physics>,
         ReadStorage<'s, PhysicsHandle>,
         WriteStorage<'s, Player>,
         ReadStorage<'s, Transform>,
         Entities<'s>,
         Write<'s, Enterprise>,
-        Read<'s, Time>,
+        Read<'s, PhysicsClock>,
         Read<'s, LazyUpdate>,
         SpriteRes<'s>,
         Read<'s, FpsCounter>,
+        Write<'s, GameRng>,
     );
 
     fn run(
@@ -106,56 +113,72 @@ impl<'s> System<'s> for PlayerMovementSystem {
             transforms,
             entities,
             mut enterprise,
-            time,
+            clock,
             update,
             sprites,
             fps,
+            mut rng,
         ): Self::SystemData,
     ) {
-        let x_tilt = input.axis_value("leftright");
-        let y_tilt = input.axis_value("updown");
-        let boost = input.action_is_down("boost").unwrap_or(false);
-        if let (Some(x_tilt), Some(y_tilt)) = (x_tilt, y_tilt) {
-            if let Some((entity, transform, handle, player)) =
-                (&entities, &transforms, &handles, &mut player)
-                    .join()
-                    .next()
-            {
-                if player.state != PlayerState::Active {
-                    return;
-                }
-                let mut burn_rate = 0.05 * y_tilt.abs() as f64;
-                if boost {
-                    burn_rate *= 2.0;
-                }
-                enterprise.eat_fuel(burn_rate, &time);
-                let position = physics.get_position(handle).unwrap();
-                let speed = if boost { 200_000.0 } else { 100_000.0 };
-                physics.apply_force(
-                    handle,
-                    position
-                        .rotation
-                        .transform_vector(&Vector2::new(0.0, y_tilt * speed)),
+        let x_tilt = input.leftright;
+        let y_tilt = input.updown;
+        let boost = input.boost;
+        if let Some((entity, transform, handle, player)) =
+            (&entities, &transforms, &handles, &mut player)
+                .join()
+                .next()
+        {
+            if player.state != PlayerState::Active {
+                return;
+            }
+            let mut burn_rate = 0.05 * y_tilt.abs() as f64;
+            if boost {
+                burn_rate *= 2.0;
+            }
+            enterprise.eat_fuel(burn_rate, clock.stepped() as f64);
+            let position = physics.get_position(handle).unwrap();
+            let speed = if boost { 200_000.0 } else { 100_000.0 };
+            physics.apply_force(
+                handle,
+                position
+                    .rotation
+                    .transform_vector(&Vector2::new(0.0, y_tilt * speed)),
+            );
+            physics.set_angular_velocity(handle, -x_tilt);
+            if rng.gen::<f64>() < burn_rate * 10.0 {
+                let location = transform.translation();
+                let mut direction = position
+                    .rotation
+                    .transform_vector(&Vector2::new(0.0, -y_tilt));
+                emit_particle(
+                    update.create_entity(&entities),
+                    sprites.get_handle(),
+                    Particle::player(direction),
+                    Point2::new(location.x, location.y),
                 );
-                physics.set_angular_velocity(handle, -x_tilt);
-                if rand::random::<f64>() < burn_rate * 10.0 {
-                    let location = transform.translation();
-                    let mut direction = position
-                        .rotation
-                        .transform_vector(&Vector2::new(0.0, -y_tilt));
-                    emit_particle(
-                        update.create_entity(&entities),
-                        sprites.get_handle(),
-                        Particle::player(direction),
-                        Point2::new(location.x, location.y),
-                    );
-                }
             }
         }
         // println!("{}", fps.sampled_fps());
     }
 }
 
+/// Tells everyone when the tank runs dry, whatever burned the last of it.
+#[derive(Default)]
+pub struct FuelWatchSystem {
+    empty: bool,
+}
+impl<'s> System<'s> for FuelWatchSystem {
+    type SystemData = (Read<'s, Enterprise>, Write<'s, EventChannel<FuelDepleted>>);
+
+    fn run(&mut self, (enterprise, mut depleted): Self::SystemData) {
+        let empty = enterprise.fuel() <= 0.0;
+        if empty && !self.empty {
+            depleted.single_write(FuelDepleted);
+        }
+        self.empty = empty;
+    }
+}
+
 pub struct PlayerBundle;
 
 impl<'a, 'b> SystemBundle<'a, 'b> for PlayerBundle {
@@ -168,8 +191,40 @@ impl<'a, 'b> SystemBundle<'a, 'b> for PlayerBundle {
         dispatcher.add(PlayerTractorSystem, "player_tractor", &[]);
         dispatcher.add(PlayerDeliverySystem, "player_delivery", &[]);
         dispatcher.add(PlayerJumpSystem, "player_jump", &["player_delivery"]);
+        dispatcher.add(
+            MarketRecoverySystem,
+            "market_recovery",
+            &["player_delivery"],
+        );
         dispatcher.add(PlayerDeliveryArrowSystem, "player_delivery_arrow", &[]);
         dispatcher.add(TractorGravitySystem, "tractor_gravity", &[]);
+        dispatcher.add(
+            FuelWatchSystem::default(),
+            "fuel_watch",
+            &["player_movement", "player_tractor", "tractor_gravity"],
+        );
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestWorldBuilder;
+
+    #[test]
+    fn running_dry_is_announced_once() {
+        let mut test = TestWorldBuilder::new()
+            .with(FuelWatchSystem::default(), "fuel_watch")
+            .build();
+        let mut reader: ReaderId<FuelDepleted> = test
+            .world
+            .write_resource::<EventChannel<FuelDepleted>>()
+            .register_reader();
+        test.run(2);
+        test.world.write_resource::<Enterprise>().burn_fuel(100.0);
+        test.run(3);
+        let channel = test.world.read_resource::<EventChannel<FuelDepleted>>();
+        assert_eq!(channel.read(&mut reader).count(), 1);
+    }
+}
@@ -0,0 +1,225 @@
+//! Uncharted sectors: levels made up on the spot, so an enterprise can keep working once it has
+//! seen the hand-made ones. Everything about a sector follows from its seed and difficulty.
+
+use std::collections::HashMap;
+
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{
+    asteroid::{Amount, AsteroidType, FieldMix},
+    economy::Unlock,
+    level::{AsteroidDesc, Level, LevelHandle, ReferenceDesc},
+    materials::Materials,
+    menu::CardDesc,
+    placement::Area,
+    rng::GameRng,
+};
+
+/// Levels an enterprise completes before uncharted sectors are offered: the tutorial and the
+/// first job.
+pub const UNCHARTED_AFTER: usize = 2;
+
+/// What every sector's name starts with.
+const SECTOR_NAME: &str = "Uncharted Sector";
+
+/// Whether a level, by name, is one made up here rather than by hand.
+pub fn is_uncharted(level: &str) -> bool {
+    level.starts_with(SECTOR_NAME)
+}
+
+/// Difficulty starts at 1 and rises by one every three levels completed after that.
+pub fn difficulty(levels_completed: usize) -> f32 {
+    1.0 + levels_completed.saturating_sub(UNCHARTED_AFTER) as f32 / 3.0
+}
+
+/// The seed for the sector offered next in a run, so the offer only changes once a level is done.
+pub fn sector_seed(run_seed: u64, levels_completed: usize) -> u64 {
+    run_seed.wrapping_add(levels_completed as u64)
+}
+
+/// The sector offered last, kept so opening the level menu again doesn't load it again.
+#[derive(Default)]
+pub struct OfferedSector(pub Option<(u64, Level, LevelHandle)>);
+
+/// Everything a sector's fields might hold beyond plain minerals, with how dense each gets.
+fn extras(difficulty: f32) -> Vec<(AsteroidType, f32)> {
+    vec![
+        (AsteroidType::Bomb, 0.02 * difficulty),
+        (AsteroidType::Hydrogen, 0.08),
+        (AsteroidType::Oxygen, 0.08),
+        (AsteroidType::Sulphur, 0.04),
+        (AsteroidType::ShipPieceTarnished(0), 0.01),
+        (AsteroidType::ShipPiece(3), 0.01),
+    ]
+}
+
+fn field(rng: &mut GameRng, difficulty: f32, (width, height): (f32, f32)) -> AsteroidDesc {
+    let area = if rng.gen() {
+        let size = (
+            width * rng.gen_range(0.5..1.2),
+            height * rng.gen_range(0.5..1.2),
+        );
+        Area::Centered {
+            center: (
+                rng.gen_range(-1.0..1.0) * (width - size.0 / 2.0),
+                rng.gen_range(-1.0..1.0) * (height - size.1 / 2.0),
+            ),
+            size,
+        }
+    } else {
+        let radius = width.min(height) * rng.gen_range(0.3..0.7);
+        Area::Circle {
+            center: (
+                rng.gen_range(-1.0..1.0) * (width - radius),
+                rng.gen_range(-1.0..1.0) * (height - radius),
+            ),
+            radius,
+        }
+    };
+    let minerals = FieldMix {
+        types: vec![
+            (AsteroidType::Big, rng.gen_range(0.05..0.2)),
+            (AsteroidType::Medium, 0.4),
+            (AsteroidType::Small, 0.4),
+            (AsteroidType::Bitty, 0.1),
+        ],
+        amount: Amount::Density(rng.gen_range(0.3..0.7)),
+    };
+    let mut mix = vec![minerals];
+    let mut extras = extras(difficulty);
+    extras.shuffle(rng);
+    for (asteroid, density) in extras.into_iter().take(rng.gen_range(1..=3)) {
+        mix.push(FieldMix {
+            types: vec![(asteroid, 1.0)],
+            amount: Amount::Density(density),
+        });
+    }
+    let speed = 5.0 * (difficulty - 1.0);
+    AsteroidDesc::Field {
+        location: None,
+        normal: None,
+        bombs: None,
+        gases: None,
+        sulphur: None,
+        artifacts: None,
+        debris: None,
+        mix,
+        area: Some(area),
+        speed: if speed > 0.0 {
+            Some((0.0, speed))
+        } else {
+            None
+        },
+        spin: if difficulty >= 2.0 {
+            Some((-0.5, 0.5))
+        } else {
+            None
+        },
+    }
+}
+
+/// A sector with room, hazards and a jump cost growing with `difficulty`, and a couple of
+/// prices off the catalogue's.
+pub fn generate_level(seed: u64, difficulty: f32, materials: &Materials) -> Level {
+    let mut rng = GameRng::new(Some(seed));
+    let name = format!("{} {:04X}", SECTOR_NAME, seed & 0xFFFF);
+    let size = (2000.0 + 500.0 * difficulty).min(6000.0);
+    let boundaries = (
+        size * rng.gen_range(0.8..1.2),
+        size * rng.gen_range(0.8..1.2),
+    );
+    let scatter = |rng: &mut GameRng| {
+        (
+            rng.gen_range(-0.6..0.6) * boundaries.0,
+            rng.gen_range(-0.6..0.6) * boundaries.1,
+        )
+    };
+
+    let mut level = Level::default();
+    level.boundaries = boundaries;
+    level.player_start = Some(scatter(&mut rng));
+    level.deliveries = (0..(1 + difficulty as usize / 3).min(3))
+        .map(|_| scatter(&mut rng))
+        .collect();
+    let fields = 1 + rng.gen_range(0..=(difficulty as usize).min(2));
+    level.asteroids = (0..fields)
+        .map(|_| field(&mut rng, difficulty, boundaries))
+        .collect();
+    if let Some(AsteroidDesc::Field { mix, .. }) = level.asteroids.first_mut() {
+        mix.push(FieldMix::new(vec![(AsteroidType::EncasedArtifact, 1.0)], 1));
+    }
+
+    // Only what's actually out there is worth repricing.
+    let mut present: Vec<AsteroidType> = level
+        .asteroids
+        .iter()
+        .filter_map(|field| match field {
+            AsteroidDesc::Field { mix, .. } => Some(mix),
+            _ => None,
+        })
+        .flatten()
+        .flat_map(|mix| mix.types.iter().map(|(asteroid, _)| asteroid.clone()))
+        .filter(|asteroid| materials.contains(asteroid))
+        .collect();
+    present.sort_by_key(|asteroid| format!("{:?}", asteroid));
+    present.dedup();
+    let mut prices = HashMap::new();
+    let mut news = Vec::new();
+    let repriced: Vec<AsteroidType> = present.choose_multiple(&mut rng, 2).cloned().collect();
+    for asteroid in repriced.iter() {
+        let factor = rng.gen_range(0.5..2.5);
+        prices.insert(asteroid.clone(), materials.get(asteroid).base_ppm * factor);
+        news.push(format!(
+            "{:?} is selling {} here.",
+            asteroid,
+            if factor >= 1.0 { "high" } else { "low" }
+        ));
+    }
+
+    level.jump_cost = ((250.0 * difficulty / 50.0).round() * 50.0) as u64;
+    level.par_time = Some(120.0 + 60.0 * fields as f32);
+    level.seed = Some(seed);
+    level.unlock = Unlock {
+        after: UNCHARTED_AFTER,
+        ..Default::default()
+    };
+    level.card = CardDesc::new(name.clone(), 0);
+    level.reference = ReferenceDesc {
+        name,
+        description: format!(
+            "An uncharted sector with {} field{} and {} delivery zone{}. {}",
+            fields,
+            if fields == 1 { "" } else { "s" },
+            level.deliveries.len(),
+            if level.deliveries.len() == 1 { "" } else { "s" },
+            news.join(" ")
+        ),
+        shown_prices: repriced,
+    };
+    level.modified_prices = Some(prices);
+    level
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::load_asset;
+
+    #[test]
+    fn sectors_are_playable_and_repeatable() {
+        let materials = load_asset::<Materials>("materials.ron");
+        for seed in 0..50 {
+            for completed in [2, 5, 11, 40] {
+                let level = generate_level(seed, difficulty(completed), &materials);
+                assert_eq!(level.problems(usize::MAX, &materials), Vec::<String>::new());
+            }
+        }
+        let easy = generate_level(7, difficulty(2), &materials);
+        let hard = generate_level(7, difficulty(20), &materials);
+        assert!(hard.jump_cost > easy.jump_cost);
+        let again = generate_level(7, difficulty(2), &materials);
+        assert_eq!(again.reference.name, easy.reference.name);
+        assert_eq!(again.reference.description, easy.reference.description);
+        assert_eq!(again.deliveries, easy.deliveries);
+    }
+}
@@ -0,0 +1,271 @@
+use std::{
+    fs::File,
+    io::Write as IoWrite,
+    ops::Deref,
+    path::{Path, PathBuf},
+};
+
+use amethyst::{
+    core::{math::Point3, Time, Transform},
+    ecs::*,
+    input::{InputHandler, StringBindings},
+    renderer::Camera,
+    window::ScreenDimensions,
+    winit::MouseButton,
+};
+
+use crate::{
+    economy::Enterprise,
+    level::Level,
+    rng::{GameRng, StartupSeed},
+};
+
+/// Everything the player systems read from the player on one frame.
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct PlayerInput {
+    pub leftright: f32,
+    pub updown: f32,
+    pub strength: f32,
+    pub attenuation: f32,
+    pub boost: bool,
+    pub deliver: bool,
+    pub tractor: bool,
+    /// The mouse position in world coordinates, if the mouse is over the window.
+    pub target: Option<(f32, f32)>,
+    pub delta_seconds: f32,
+}
+
+impl PlayerInput {
+    fn capture(world: &mut World) -> Self {
+        world.exec(
+            |(input, cameras, transforms, dimensions, time): (
+                Option<Read<InputHandler<StringBindings>>>,
+                ReadStorage<Camera>,
+                ReadStorage<Transform>,
+                Option<Read<ScreenDimensions>>,
+                Read<Time>,
+            )| {
+                let input = match input {
+                    Some(input) => input,
+                    None => {
+                        return PlayerInput {
+                            delta_seconds: time.delta_seconds(),
+                            ..PlayerInput::default()
+                        }
+                    }
+                };
+                let target = match ((&transforms, &cameras).join().next(), dimensions) {
+                    (Some((transform, camera)), Some(dimensions)) => {
+                        input.mouse_position().map(|(x, y)| {
+                            let point = camera.screen_to_world_point(
+                                Point3::new(x, y, 0.0),
+                                dimensions.diagonal(),
+                                transform,
+                            );
+                            (point.x, point.y)
+                        })
+                    }
+                    _ => None,
+                };
+                PlayerInput {
+                    leftright: input.axis_value("leftright").unwrap_or(0.0),
+                    updown: input.axis_value("updown").unwrap_or(0.0),
+                    strength: input.axis_value("strength").unwrap_or(0.0),
+                    attenuation: input.axis_value("attenuation").unwrap_or(0.0),
+                    boost: input.action_is_down("boost").unwrap_or(false),
+                    deliver: input.action_is_down("deliver").unwrap_or(false),
+                    tractor: input.mouse_button_is_down(MouseButton::Left),
+                    target,
+                    delta_seconds: time.delta_seconds(),
+                }
+            },
+        )
+    }
+
+    pub fn target(&self) -> Option<Point3<f32>> {
+        self.target.map(|(x, y)| Point3::new(x, y, 0.0))
+    }
+}
+
+/// One level's worth of input, with what's needed to start the level exactly as it was.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Replay {
+    pub level: String,
+    pub seed: u64,
+    pub enterprise: Enterprise,
+    pub frames: Vec<PlayerInput>,
+    pub checksum: Option<u64>,
+}
+
+impl Replay {
+    pub fn load(path: &Path) -> amethyst::Result<Self> {
+        let file = File::open(path)?;
+        Ok(ron::de::from_reader(file)?)
+    }
+
+    fn save(&self, path: &Path) -> amethyst::Result<()> {
+        let mut file = File::create(path)?;
+        file.write_all(ron::ser::to_string(self)?.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// Condenses the end state of a level, so a replay can tell whether it went the same way.
+pub fn checksum(enterprise: &Enterprise) -> u64 {
+    enterprise.funds().rotate_left(32) ^ enterprise.fuel().to_bits()
+}
+
+/// Chosen at startup. Recording overwrites its file at the end of every level.
+pub enum ReplayMode {
+    Live,
+    Record(PathBuf),
+    Playback(Replay),
+}
+
+impl Default for ReplayMode {
+    fn default() -> Self {
+        ReplayMode::Live
+    }
+}
+
+#[derive(Default)]
+struct ActiveReplay {
+    replay: Option<Replay>,
+    frame: usize,
+}
+
+/// Puts the recorded seed and enterprise in place. Call before `initialize_level`.
+pub fn prepare_replay(world: &mut World) {
+    let playback = match &*world.read_resource::<ReplayMode>() {
+        ReplayMode::Playback(replay) => Some((replay.seed, replay.enterprise.clone())),
+        _ => None,
+    };
+    if let Some((seed, enterprise)) = playback {
+        world.insert(StartupSeed(Some(seed)));
+        world.insert(enterprise);
+    }
+}
+
+/// Starts recording or playing back the level that was just initialized.
+pub fn begin_replay(world: &mut World) {
+    world.insert(PlayerInput::default());
+    let replay = match &*world.read_resource::<ReplayMode>() {
+        ReplayMode::Live => None,
+        ReplayMode::Record(_) => Some(Replay {
+            level: world.read_resource::<Level>().reference.name.clone(),
+            seed: world.read_resource::<GameRng>().seed(),
+            enterprise: world.read_resource::<Enterprise>().deref().clone(),
+            frames: Vec::new(),
+            checksum: None,
+        }),
+        ReplayMode::Playback(replay) => Some(replay.clone()),
+    };
+    world.insert(ActiveReplay { replay, frame: 0 });
+}
+
+/// Sets this frame's `PlayerInput`, either from the live input or from the replay. Playback also
+/// restores the recorded frame time. Call from the state's `update`, before the systems run.
+pub fn next_input(world: &mut World) {
+    let playing = matches!(
+        *world.read_resource::<ReplayMode>(),
+        ReplayMode::Playback(_)
+    );
+    let input = if playing {
+        let mut active = world.write_resource::<ActiveReplay>();
+        let frame = active.frame;
+        active.frame += 1;
+        active
+            .replay
+            .as_ref()
+            .and_then(|replay| replay.frames.get(frame).cloned())
+    } else {
+        None
+    };
+    let input = match input {
+        Some(input) => {
+            world
+                .write_resource::<Time>()
+                .set_delta_seconds(input.delta_seconds);
+            input
+        }
+        None if playing => PlayerInput {
+            delta_seconds: world.read_resource::<Time>().delta_seconds(),
+            ..PlayerInput::default()
+        },
+        None => {
+            let input = PlayerInput::capture(world);
+            if let ReplayMode::Record(_) = *world.read_resource::<ReplayMode>() {
+                if let Some(replay) = world.write_resource::<ActiveReplay>().replay.as_mut() {
+                    replay.frames.push(input.clone());
+                }
+            }
+            input
+        }
+    };
+    world.insert(input);
+}
+
+/// How a played back level ended up compared to its recording.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReplayVerdict {
+    pub level: String,
+    pub expected: Option<u64>,
+    pub checksum: u64,
+}
+
+impl ReplayVerdict {
+    pub fn matched(&self) -> bool {
+        self.expected == Some(self.checksum)
+    }
+}
+
+impl std::fmt::Display for ReplayVerdict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.matched() {
+            write!(
+                f,
+                "Replay of {} matched (checksum {:x})",
+                self.level, self.checksum
+            )
+        } else {
+            write!(
+                f,
+                "Replay of {} diverged: expected checksum {:x?}, got {:x}",
+                self.level, self.expected, self.checksum
+            )
+        }
+    }
+}
+
+/// Saves the recording, or checks the playback against it and returns how that went. Playback
+/// only covers one level, so later levels go back to live input.
+pub fn finish_replay(world: &mut World) -> Option<ReplayVerdict> {
+    let replay = world.write_resource::<ActiveReplay>().replay.take();
+    let mut replay = replay?;
+    let checksum = checksum(&world.read_resource::<Enterprise>());
+    let mut mode = world.write_resource::<ReplayMode>();
+    match &*mode {
+        ReplayMode::Record(path) => {
+            replay.checksum = Some(checksum);
+            if let Err(error) = replay.save(path) {
+                log::error!("Could not save replay to {:?}: {}", path, error);
+            }
+            None
+        }
+        ReplayMode::Playback(_) => {
+            let verdict = ReplayVerdict {
+                level: replay.level,
+                expected: replay.checksum,
+                checksum,
+            };
+            if verdict.matched() {
+                log::info!("{}", verdict);
+            } else {
+                log::warn!("{}", verdict);
+            }
+            *mode = ReplayMode::Live;
+            Some(verdict)
+        }
+        ReplayMode::Live => None,
+    }
+}
@@ -0,0 +1,212 @@
+use std::{
+    fmt, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+use crate::economy::Enterprise;
+
+/// Bump this whenever `Enterprise` changes in a way old saves need help with, and add a step to
+/// `migrate`.
+pub const SAVE_VERSION: u32 = 3;
+
+pub const SAVE_SLOTS: [&str; 5] = ["slot1", "slot2", "slot3", "slot4", "slot5"];
+
+#[derive(Serialize, Deserialize)]
+struct SaveFile {
+    version: u32,
+    enterprise: Enterprise,
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(io::Error),
+    Corrupt(String),
+    TooNew(u32),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Io(error) => write!(f, "{}", error),
+            SaveError::Corrupt(error) => write!(f, "corrupt save: {}", error),
+            SaveError::TooNew(version) => write!(
+                f,
+                "save version {} is newer than this game ({})",
+                version, SAVE_VERSION
+            ),
+        }
+    }
+}
+
+impl From<io::Error> for SaveError {
+    fn from(error: io::Error) -> Self {
+        SaveError::Io(error)
+    }
+}
+
+/// The slot the current enterprise is saved into.
+#[derive(Clone, Default)]
+pub struct SaveSlot(pub String);
+
+pub fn save_dir() -> PathBuf {
+    dirs::data_dir()
+        .map(|dir| dir.join("LudumDare49"))
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("saves")
+}
+
+fn slot_path(slot: &str) -> PathBuf {
+    save_dir().join(format!("{}.ron", slot))
+}
+
+/// Upgrades a save one version at a time until it matches `SAVE_VERSION`.
+fn migrate(mut save: SaveFile) -> SaveFile {
+    while save.version < SAVE_VERSION {
+        // Version 0 was a bare `Enterprise`; wrapping it was the whole change. Version 1 kept
+        // `last_refueling`, which is now worked out from the ledger, and `last_completions`,
+        // which the enterprise reads under its new name. Version 2 counted loans but not debt.
+        if save.version == 2 {
+            save.enterprise.count_old_loans_as_debt();
+        }
+        save.version += 1;
+    }
+    save
+}
+
+fn parse(bytes: &[u8]) -> Result<SaveFile, SaveError> {
+    match ron::de::from_bytes::<SaveFile>(bytes) {
+        Ok(save) if save.version > SAVE_VERSION => Err(SaveError::TooNew(save.version)),
+        Ok(save) => Ok(save),
+        Err(error) => {
+            // Saves from before versioning held one line with the enterprise alone.
+            let first_line = bytes.split(|byte| *byte == b'\n').next().unwrap_or(bytes);
+            ron::de::from_bytes::<Enterprise>(first_line)
+                .map(|enterprise| SaveFile {
+                    version: 0,
+                    enterprise,
+                })
+                .map_err(|_| SaveError::Corrupt(error.to_string()))
+        }
+    }
+}
+
+/// Reads a slot, returning `Ok(None)` if nothing has been saved there yet.
+pub fn load_slot(slot: &str) -> Result<Option<Enterprise>, SaveError> {
+    let path = slot_path(slot);
+    if !path.exists() && slot == SAVE_SLOTS[0] {
+        // The first slot picks up a save left in the working directory by older builds.
+        let legacy = PathBuf::from("enterprise.ron");
+        if legacy.exists() {
+            return Ok(Some(migrate(parse(&fs::read(legacy)?)?).enterprise));
+        }
+    }
+    if !path.exists() {
+        return Ok(None);
+    }
+    Ok(Some(migrate(parse(&fs::read(path)?)?).enterprise))
+}
+
+/// Writes to a temporary file and renames it over the old one, so a crash never leaves half a
+/// file behind.
+pub fn write_atomically<T: Serialize>(path: &Path, value: &T) -> Result<(), SaveError> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let contents = ron::ser::to_string(value).map_err(|error| {
+        SaveError::Io(io::Error::new(
+            io::ErrorKind::InvalidData,
+            error.to_string(),
+        ))
+    })?;
+    let temp_path = path.with_extension("ron.tmp");
+    {
+        let mut file = fs::File::create(&temp_path)?;
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()?;
+    }
+    fs::rename(temp_path, path)?;
+    Ok(())
+}
+
+pub fn write_slot(slot: &str, enterprise: &Enterprise) -> Result<(), SaveError> {
+    let save = SaveFile {
+        version: SAVE_VERSION,
+        enterprise: enterprise.clone(),
+    };
+    write_atomically(&slot_path(slot), &save)
+}
+
+/// Moves an unreadable save aside so starting over in its slot doesn't destroy it.
+pub fn set_aside(slot: &str) -> Result<(), SaveError> {
+    let path = slot_path(slot);
+    if path.exists() {
+        fs::rename(&path, path.with_extension("ron.corrupt"))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::economy::{Unlock, LOAN_AMOUNT};
+
+    #[test]
+    fn saves_round_trip_at_the_current_version() {
+        let save = SaveFile {
+            version: SAVE_VERSION,
+            enterprise: Enterprise::begin_enterprise(),
+        };
+        let text = ron::ser::to_string(&save).unwrap();
+        let loaded = migrate(parse(text.as_bytes()).unwrap());
+        assert_eq!(loaded.version, SAVE_VERSION);
+        assert_eq!(loaded.enterprise.fuel(), 100.0);
+    }
+
+    #[test]
+    fn bare_enterprises_are_migrated() {
+        let text = ron::ser::to_string(&Enterprise::begin_enterprise()).unwrap();
+        let save = parse(format!("{}\nleftover", text).as_bytes()).unwrap();
+        assert_eq!(save.version, 0);
+        assert_eq!(migrate(save).version, SAVE_VERSION);
+    }
+
+    #[test]
+    fn version_one_saves_keep_their_completions() {
+        let text = "(version: 1, enterprise: (fuel: 80.0, funds: 300, loans: 0, bankruptcies: 0, \
+                    tried_jump: None, last_refueling: (200, false), \
+                    last_completions: [\"Tutorial\"]))";
+        let save = migrate(parse(text.as_bytes()).unwrap());
+        assert_eq!(save.version, SAVE_VERSION);
+        assert_eq!(save.enterprise.funds(), 300);
+        let after_tutorial = Unlock {
+            after: 1,
+            ..Unlock::default()
+        };
+        assert!(save.enterprise.offers("Striking Out!", &after_tutorial));
+    }
+
+    #[test]
+    fn version_two_loans_become_debt() {
+        let text = "(version: 2, enterprise: (fuel: 100.0, funds: 0, loans: 2, bankruptcies: 0, \
+                    tried_jump: None))";
+        let save = migrate(parse(text.as_bytes()).unwrap());
+        assert_eq!(save.enterprise.debt(), 2 * LOAN_AMOUNT);
+    }
+
+    #[test]
+    fn unreadable_saves_are_reported() {
+        assert!(matches!(
+            parse(b"(version: 1, enterprise: (fuel: "),
+            Err(SaveError::Corrupt(_))
+        ));
+        let save = SaveFile {
+            version: SAVE_VERSION + 1,
+            enterprise: Enterprise::begin_enterprise(),
+        };
+        let text = ron::ser::to_string(&save).unwrap();
+        assert!(matches!(parse(text.as_bytes()), Err(SaveError::TooNew(_))));
+    }
+}
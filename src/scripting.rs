@@ -0,0 +1,433 @@
+//! Level scripts, written in Rhai, for what level data can't say on its own: waves of asteroids,
+//! timed events and prices that move. A level names its script file and the script can define any
+//! of these callbacks:
+//!
+//! - `on_start()`
+//! - `on_tick(elapsed)`
+//! - `on_deliver(asteroid, mass, income)`
+//! - `on_reaction(first, second)`
+//!
+//! `this` is a map that keeps whatever the callbacks put in it for the rest of the level. Scripts
+//! can call `spawn_asteroid(asteroid, x, y)`, `set_price(asteroid, price)`, `pay(amount)` and
+//! `message(text)`, and read `funds()`, `fuel()`, `debt()` and `elapsed()`. Asteroids are named as
+//! in level files, like `"WaterBig"`.
+
+use std::{
+    fs,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use amethyst::{
+    core::{SystemBundle, Transform},
+    ecs::*,
+    prelude::*,
+    shrev::{EventChannel, ReaderId},
+    Error,
+};
+use rhai::{
+    module_resolvers::DummyModuleResolver, CallFnOptions, Dynamic, Engine, FuncArgs, Map, Scope,
+    AST,
+};
+
+use crate::{
+    assets::SpriteRes,
+    asteroid::{generate_asteroid, AsteroidType},
+    economy::{Enterprise, Transaction},
+    events::{AsteroidDelivered, AsteroidReacted},
+    level::{Level, LevelTimer},
+    materials::Materials,
+    tutorial::TutorialProgress,
+};
+
+/// How much work a single callback may do before it's stopped.
+const MAX_OPERATIONS: u64 = 100_000;
+
+/// What a script asked for, done once its callback returns.
+#[derive(Clone, Debug, PartialEq)]
+enum ScriptCommand {
+    Spawn {
+        asteroid: AsteroidType,
+        location: (f32, f32),
+    },
+    SetPrice {
+        asteroid: AsteroidType,
+        price: f32,
+    },
+    Pay {
+        amount: u64,
+    },
+    Message(String),
+}
+
+/// What scripts can see of the game, as of the callback being made.
+#[derive(Default, Clone, Copy)]
+struct ScriptView {
+    funds: u64,
+    fuel: f64,
+    debt: u64,
+    elapsed: f32,
+}
+
+#[derive(Default)]
+struct Shared {
+    view: ScriptView,
+    commands: Vec<ScriptCommand>,
+}
+
+fn asteroid_named(name: &str) -> Option<AsteroidType> {
+    ron::de::from_str(name).ok()
+}
+
+fn asteroid_name(asteroid: &AsteroidType) -> String {
+    ron::ser::to_string(asteroid).unwrap_or_default()
+}
+
+/// Scripts get no modules, files or unbounded loops: only the game API and Rhai's own language.
+fn sandboxed_engine(shared: &Arc<Mutex<Shared>>) -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .set_module_resolver(DummyModuleResolver::new())
+        .set_max_operations(MAX_OPERATIONS)
+        .set_max_call_levels(32)
+        .set_max_expr_depths(64, 32)
+        .set_max_string_size(1024)
+        .set_max_array_size(1024)
+        .set_max_map_size(1024);
+
+    let commands = shared.clone();
+    engine.register_fn(
+        "spawn_asteroid",
+        move |asteroid: &str, x: f64, y: f64| match asteroid_named(asteroid) {
+            Some(asteroid) => commands
+                .lock()
+                .unwrap()
+                .commands
+                .push(ScriptCommand::Spawn {
+                    asteroid,
+                    location: (x as f32, y as f32),
+                }),
+            None => log::warn!("Level script tried to spawn unknown asteroid {}", asteroid),
+        },
+    );
+    let commands = shared.clone();
+    engine.register_fn(
+        "set_price",
+        move |asteroid: &str, price: f64| match asteroid_named(asteroid) {
+            Some(asteroid) => commands
+                .lock()
+                .unwrap()
+                .commands
+                .push(ScriptCommand::SetPrice {
+                    asteroid,
+                    price: price.max(0.0) as f32,
+                }),
+            None => log::warn!("Level script tried to price unknown asteroid {}", asteroid),
+        },
+    );
+    let commands = shared.clone();
+    engine.register_fn("pay", move |amount: i64| {
+        commands.lock().unwrap().commands.push(ScriptCommand::Pay {
+            amount: amount.max(0) as u64,
+        });
+    });
+    let commands = shared.clone();
+    engine.register_fn("message", move |text: &str| {
+        commands
+            .lock()
+            .unwrap()
+            .commands
+            .push(ScriptCommand::Message(text.to_string()));
+    });
+
+    let view = shared.clone();
+    engine.register_fn("funds", move || view.lock().unwrap().view.funds as i64);
+    let view = shared.clone();
+    engine.register_fn("fuel", move || view.lock().unwrap().view.fuel);
+    let view = shared.clone();
+    engine.register_fn("debt", move || view.lock().unwrap().view.debt as i64);
+    let view = shared.clone();
+    engine.register_fn("elapsed", move || view.lock().unwrap().view.elapsed as f64);
+    engine
+}
+
+/// The script for the level in progress.
+pub struct LevelScript {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    memory: Dynamic,
+    shared: Arc<Mutex<Shared>>,
+    started: bool,
+    /// Set after the script fails, so one broken script doesn't fill the log every frame.
+    stopped: bool,
+}
+
+impl LevelScript {
+    pub fn compile(source: &str) -> Result<Self, String> {
+        let shared = Arc::new(Mutex::new(Shared::default()));
+        let engine = sandboxed_engine(&shared);
+        let ast = engine.compile(source).map_err(|error| error.to_string())?;
+        Ok(LevelScript {
+            engine,
+            ast,
+            scope: Scope::new(),
+            memory: Dynamic::from_map(Map::new()),
+            shared,
+            started: false,
+            stopped: false,
+        })
+    }
+
+    /// Reads and compiles a script, with `path` relative to the assets directory.
+    pub fn load(assets_dir: &Path, path: &str) -> Result<Self, String> {
+        let source = fs::read_to_string(assets_dir.join(path))
+            .map_err(|error| format!("{}: {}", path, error))?;
+        Self::compile(&source).map_err(|error| format!("{}: {}", path, error))
+    }
+
+    fn defines(&self, name: &str, parameters: usize) -> bool {
+        self.ast
+            .iter_functions()
+            .any(|function| function.name == name && function.params.len() == parameters)
+    }
+
+    /// Calls a callback if the script has it, returning whatever it asked for.
+    fn call(
+        &mut self,
+        view: ScriptView,
+        name: &str,
+        parameters: usize,
+        args: impl FuncArgs,
+    ) -> Vec<ScriptCommand> {
+        if self.stopped || !self.defines(name, parameters) {
+            return Vec::new();
+        }
+        self.shared.lock().unwrap().view = view;
+        let options = CallFnOptions::new()
+            .eval_ast(false)
+            .bind_this_ptr(&mut self.memory);
+        let result = self.engine.call_fn_with_options::<Dynamic>(
+            options,
+            &mut self.scope,
+            &self.ast,
+            name,
+            args,
+        );
+        let commands = std::mem::take(&mut self.shared.lock().unwrap().commands);
+        match result {
+            Ok(_) => commands,
+            Err(error) => {
+                log::error!("Level script stopped in {}: {}", name, error);
+                self.stopped = true;
+                Vec::new()
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct LevelScriptSystem {
+    delivered: Option<ReaderId<AsteroidDelivered>>,
+    reacted: Option<ReaderId<AsteroidReacted>>,
+}
+
+impl<'s> System<'s> for LevelScriptSystem {
+    type SystemData = (
+        Option<Write<'s, LevelScript>>,
+        Read<'s, EventChannel<AsteroidDelivered>>,
+        Read<'s, EventChannel<AsteroidReacted>>,
+        Write<'s, Level>,
+        Write<'s, Enterprise>,
+        Read<'s, LevelTimer>,
+        Write<'s, TutorialProgress>,
+        Read<'s, LazyUpdate>,
+        Entities<'s>,
+        SpriteRes<'s>,
+        Read<'s, Materials>,
+    );
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.delivered = Some(
+            world
+                .write_resource::<EventChannel<AsteroidDelivered>>()
+                .register_reader(),
+        );
+        self.reacted = Some(
+            world
+                .write_resource::<EventChannel<AsteroidReacted>>()
+                .register_reader(),
+        );
+    }
+
+    fn run(
+        &mut self,
+        (
+            script,
+            delivered,
+            reacted,
+            mut level,
+            mut enterprise,
+            timer,
+            mut progress,
+            update,
+            entities,
+            sprites,
+            materials,
+        ): Self::SystemData,
+    ) {
+        // Events are still read without a script, so a level's script never hears about the last.
+        let delivered: Vec<AsteroidDelivered> =
+            self.delivered.as_mut().map_or(Vec::new(), |reader| {
+                delivered.read(reader).cloned().collect()
+            });
+        let reacted: Vec<AsteroidReacted> = self
+            .reacted
+            .as_mut()
+            .map_or(Vec::new(), |reader| reacted.read(reader).cloned().collect());
+        let mut script = match script {
+            Some(script) => script,
+            None => return,
+        };
+        let view = ScriptView {
+            funds: enterprise.funds(),
+            fuel: enterprise.fuel(),
+            debt: enterprise.debt(),
+            elapsed: timer.elapsed,
+        };
+
+        let mut commands = Vec::new();
+        if !script.started {
+            script.started = true;
+            commands.extend(script.call(view, "on_start", 0, ()));
+        }
+        for event in delivered {
+            commands.extend(script.call(
+                view,
+                "on_deliver",
+                3,
+                (
+                    asteroid_name(&event.asteroid),
+                    event.mass as f64,
+                    event.income as i64,
+                ),
+            ));
+        }
+        for event in reacted {
+            commands.extend(script.call(
+                view,
+                "on_reaction",
+                2,
+                (
+                    asteroid_name(&event.reactants.0),
+                    asteroid_name(&event.reactants.1),
+                ),
+            ));
+        }
+        commands.extend(script.call(view, "on_tick", 1, (timer.elapsed as f64,)));
+
+        for command in commands {
+            match command {
+                ScriptCommand::Spawn { asteroid, location } => {
+                    if let Some(sprites) = &sprites {
+                        let mut transform = Transform::default();
+                        transform.set_translation_xyz(location.0, location.1, 0.0);
+                        generate_asteroid(
+                            update.create_entity(&entities),
+                            sprites.sprites.clone(),
+                            &materials,
+                            asteroid,
+                            transform,
+                        );
+                    }
+                }
+                ScriptCommand::SetPrice { asteroid, price } => level.set_price(asteroid, price),
+                ScriptCommand::Pay { amount } => enterprise.apply(Transaction::Bonus { amount }),
+                ScriptCommand::Message(text) => progress.text = text,
+            }
+        }
+    }
+}
+
+pub struct ScriptingBundle;
+impl<'a, 'b> SystemBundle<'a, 'b> for ScriptingBundle {
+    fn build(
+        self,
+        _world: &mut World,
+        dispatcher: &mut DispatcherBuilder<'a, 'b>,
+    ) -> Result<(), Error> {
+        dispatcher.add(LevelScriptSystem::default(), "level_script", &[]);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        asteroid::Asteroid,
+        test_support::{TestWorld, TestWorldBuilder},
+    };
+
+    fn run_script(source: &str, ticks: usize) -> TestWorld {
+        let mut test = TestWorldBuilder::new()
+            .with(LevelScriptSystem::default(), "level_script")
+            .build();
+        test.world.register::<Asteroid>();
+        test.world.insert(LevelScript::compile(source).unwrap());
+        test.run(ticks);
+        test
+    }
+
+    #[test]
+    fn callbacks_drive_the_game_api() {
+        let test = run_script(
+            r#"
+            fn on_start() {
+                this.waves = 0;
+                set_price("Big", 9.5);
+                message("Incoming");
+            }
+            fn on_tick(elapsed) {
+                if this.waves < 2 {
+                    this.waves += 1;
+                    spawn_asteroid("Bomb", 10.0 * this.waves, 0.0);
+                    pay(funds() + 5);
+                }
+            }
+            "#,
+            4,
+        );
+        test.assert_asteroids(&[AsteroidType::Bomb, AsteroidType::Bomb]);
+        let materials = test.world.read_resource::<Materials>();
+        assert_eq!(
+            test.world
+                .read_resource::<Level>()
+                .get_ppm(&materials, &AsteroidType::Big),
+            9.5
+        );
+        // The first payment doubles the starting funds plus five, the second does it again.
+        let start = Enterprise::begin_enterprise().funds();
+        assert_eq!(test.funds(), (start * 2 + 5) * 2 + 5);
+        assert_eq!(
+            test.world.read_resource::<TutorialProgress>().text,
+            "Incoming"
+        );
+    }
+
+    #[test]
+    fn runaway_scripts_are_stopped() {
+        let test = run_script("fn on_tick(elapsed) { loop { pay(1); } }", 2);
+        let script = test.world.read_resource::<LevelScript>();
+        assert!(script.stopped);
+        assert_eq!(test.funds(), Enterprise::begin_enterprise().funds());
+    }
+
+    #[test]
+    fn unknown_asteroids_are_not_spawned() {
+        assert_eq!(asteroid_named("Unobtainium"), None);
+        assert_eq!(asteroid_named("WaterBig"), Some(AsteroidType::WaterBig));
+        assert_eq!(asteroid_name(&AsteroidType::AcidBig), "AcidBig");
+    }
+}
@@ -0,0 +1,302 @@
+//! Running totals of what has been done, kept for the enterprise in each save slot and for every
+//! enterprise together.
+
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use amethyst::{
+    core::SystemBundle,
+    ecs::*,
+    prelude::*,
+    shrev::{EventChannel, ReaderId},
+    ui::{UiText, UiTransform},
+    Error,
+};
+
+use crate::{
+    asteroid::AsteroidType,
+    economy::LevelSummary,
+    events::{AsteroidDelivered, AsteroidReacted, ExplosionTriggered},
+    menu::find_by_id,
+    procgen::is_uncharted,
+    save::{save_dir, write_atomically, SaveError},
+};
+
+/// Where best earnings in any uncharted sector are kept.
+const UNCHARTED_EARNINGS: &str = "Uncharted Sectors";
+
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
+pub struct Stats {
+    #[serde(default)]
+    pub delivered_mass: HashMap<AsteroidType, f32>,
+    #[serde(default)]
+    pub explosions: usize,
+    #[serde(default)]
+    pub reactions: usize,
+    #[serde(default)]
+    pub fuel_burned: f64,
+    #[serde(default)]
+    pub loans_taken: usize,
+    /// The most earned from deliveries in one go at each level, by name. Uncharted sectors are
+    /// all kept together, as there's no end to them.
+    #[serde(default)]
+    pub best_earnings: HashMap<String, u64>,
+}
+
+impl Stats {
+    fn finish_level(&mut self, level: &str, summary: &LevelSummary) {
+        self.fuel_burned += summary.fuel_burned;
+        self.loans_taken += summary.loans_taken;
+        let level = if is_uncharted(level) {
+            UNCHARTED_EARNINGS
+        } else {
+            level
+        };
+        let best = self.best_earnings.entry(level.to_string()).or_insert(0);
+        *best = u64::max(*best, summary.income);
+    }
+
+    /// A few lines for the stats screen.
+    pub fn describe(&self) -> String {
+        let mut delivered: Vec<_> = self.delivered_mass.iter().collect();
+        delivered.sort_by_key(|(asteroid, _)| format!("{:?}", asteroid));
+        let delivered: Vec<String> = delivered
+            .iter()
+            .map(|(asteroid, mass)| format!("{:.0} {:?}", mass, asteroid))
+            .collect();
+        let mut earnings: Vec<_> = self.best_earnings.iter().collect();
+        earnings.sort();
+        let earnings: Vec<String> = earnings
+            .iter()
+            .map(|(level, earned)| format!("{} {}", level, earned))
+            .collect();
+        format!(
+            "Delivered: {}\nExplosions: {} - Reactions: {}\nFuel burned: {:.0} - Loans taken: {}\nBest earnings: {}",
+            if delivered.is_empty() {
+                "nothing yet".to_string()
+            } else {
+                delivered.join(", ")
+            },
+            self.explosions,
+            self.reactions,
+            self.fuel_burned,
+            self.loans_taken,
+            if earnings.is_empty() {
+                "none yet".to_string()
+            } else {
+                earnings.join(", ")
+            },
+        )
+    }
+}
+
+fn stats_path(name: &str) -> PathBuf {
+    save_dir().join(format!("{}.ron", name))
+}
+
+fn load_stats(name: &str) -> Result<Stats, SaveError> {
+    let path = stats_path(name);
+    if !path.exists() {
+        return Ok(Stats::default());
+    }
+    ron::de::from_bytes(&fs::read(path)?).map_err(|error| SaveError::Corrupt(error.to_string()))
+}
+
+/// The stats for the enterprise in the chosen slot, and for every enterprise.
+#[derive(Default)]
+pub struct Statistics {
+    pub enterprise: Stats,
+    pub global: Stats,
+}
+
+impl Statistics {
+    /// Reads the stats kept alongside a save slot. Unreadable stats start over rather than getting
+    /// in the way of playing.
+    pub fn load(slot: &str) -> Self {
+        let load = |name: &str| {
+            load_stats(name).unwrap_or_else(|error| {
+                log::warn!("Could not load stats {}: {}", name, error);
+                Stats::default()
+            })
+        };
+        Statistics {
+            enterprise: load(&format!("{}.stats", slot)),
+            global: load("stats"),
+        }
+    }
+
+    pub fn save(&self, slot: &str) {
+        for (name, stats) in [
+            (format!("{}.stats", slot), &self.enterprise),
+            ("stats".to_string(), &self.global),
+        ] {
+            if let Err(error) = write_atomically(&stats_path(&name), stats) {
+                log::error!("Could not save stats {}: {}", name, error);
+            }
+        }
+    }
+
+    fn record(&mut self, change: impl Fn(&mut Stats)) {
+        change(&mut self.enterprise);
+        change(&mut self.global);
+    }
+
+    /// Adds what only adds up at the end of a level: fuel, loans and earnings.
+    pub fn finish_level(&mut self, level: &str, summary: &LevelSummary) {
+        self.record(|stats| stats.finish_level(level, summary));
+    }
+}
+
+#[derive(Default)]
+pub struct StatsSystem {
+    delivered: Option<ReaderId<AsteroidDelivered>>,
+    reacted: Option<ReaderId<AsteroidReacted>>,
+    exploded: Option<ReaderId<ExplosionTriggered>>,
+}
+
+impl<'s> System<'s> for StatsSystem {
+    type SystemData = (
+        Read<'s, EventChannel<AsteroidDelivered>>,
+        Read<'s, EventChannel<AsteroidReacted>>,
+        Read<'s, EventChannel<ExplosionTriggered>>,
+        Write<'s, Statistics>,
+    );
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.delivered = Some(
+            world
+                .write_resource::<EventChannel<AsteroidDelivered>>()
+                .register_reader(),
+        );
+        self.reacted = Some(
+            world
+                .write_resource::<EventChannel<AsteroidReacted>>()
+                .register_reader(),
+        );
+        self.exploded = Some(
+            world
+                .write_resource::<EventChannel<ExplosionTriggered>>()
+                .register_reader(),
+        );
+    }
+
+    fn run(&mut self, (delivered, reacted, exploded, mut statistics): Self::SystemData) {
+        if let Some(reader) = &mut self.delivered {
+            for event in delivered.read(reader) {
+                statistics.record(|stats| {
+                    *stats
+                        .delivered_mass
+                        .entry(event.asteroid.clone())
+                        .or_insert(0.0) += event.mass;
+                });
+            }
+        }
+        if let Some(reader) = &mut self.reacted {
+            for _ in reacted.read(reader) {
+                statistics.record(|stats| stats.reactions += 1);
+            }
+        }
+        if let Some(reader) = &mut self.exploded {
+            for _ in exploded.read(reader) {
+                statistics.record(|stats| stats.explosions += 1);
+            }
+        }
+    }
+}
+
+/// Fills in the stats screen, when it's showing. Only added with a window to show it in.
+pub struct StatsScreenSystem;
+impl<'s> System<'s> for StatsScreenSystem {
+    type SystemData = (
+        Entities<'s>,
+        WriteStorage<'s, UiTransform>,
+        WriteStorage<'s, UiText>,
+        Read<'s, Statistics>,
+    );
+
+    fn run(&mut self, (entities, transforms, mut texts, statistics): Self::SystemData) {
+        for (id, stats) in [
+            ("stats_enterprise", &statistics.enterprise),
+            ("stats_global", &statistics.global),
+        ] {
+            if let Some(label) = find_by_id(&entities, &transforms, id) {
+                if let Some(text) = texts.get_mut(label) {
+                    text.text = stats.describe();
+                }
+            }
+        }
+    }
+}
+
+pub struct StatsBundle;
+impl<'a, 'b> SystemBundle<'a, 'b> for StatsBundle {
+    fn build(
+        self,
+        _world: &mut World,
+        dispatcher: &mut DispatcherBuilder<'a, 'b>,
+    ) -> Result<(), Error> {
+        dispatcher.add(StatsSystem::default(), "stats", &[]);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{asteroid::AsteroidReactionSystem, test_support::TestWorldBuilder};
+
+    #[test]
+    fn reactions_and_explosions_are_counted() {
+        let mut test = TestWorldBuilder::new()
+            .with(AsteroidReactionSystem::default(), "asteroid_react")
+            .with(StatsSystem::default(), "stats")
+            .build();
+        test.spawn_asteroid(AsteroidType::Hydrogen, (0.0, 0.0));
+        test.spawn_asteroid(AsteroidType::Oxygen, (4.0, 0.0));
+        test.world
+            .write_resource::<EventChannel<ExplosionTriggered>>()
+            .single_write(ExplosionTriggered {
+                position: (0.0, 0.0),
+                strength: 1.0,
+            });
+        test.run(3);
+        let statistics = test.world.read_resource::<Statistics>();
+        assert_eq!(statistics.enterprise.reactions, 1);
+        assert_eq!(statistics.global.explosions, 1);
+    }
+
+    #[test]
+    fn levels_keep_their_best_earnings() {
+        let mut statistics = Statistics::default();
+        let summary = |income| LevelSummary {
+            income,
+            fuel_burned: 10.0,
+            ..LevelSummary::default()
+        };
+        statistics.finish_level("Level", &summary(300));
+        statistics.finish_level("Level", &summary(200));
+        assert_eq!(statistics.global.best_earnings["Level"], 300);
+        assert_eq!(statistics.enterprise.fuel_burned, 20.0);
+    }
+
+    #[test]
+    fn uncharted_sectors_share_their_best_earnings() {
+        let mut statistics = Statistics::default();
+        for (seed, income) in [(1, 100), (2, 400), (3, 200)] {
+            let sector = crate::procgen::generate_level(
+                seed,
+                1.0,
+                &crate::test_support::load_asset("materials.ron"),
+            );
+            statistics.finish_level(
+                &sector.reference.name,
+                &LevelSummary {
+                    income,
+                    ..LevelSummary::default()
+                },
+            );
+        }
+        assert_eq!(statistics.global.best_earnings.len(), 1);
+        assert_eq!(statistics.global.best_earnings[UNCHARTED_EARNINGS], 400);
+    }
+}
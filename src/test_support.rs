@@ -0,0 +1,200 @@
+//! Small simulations for tests: a world with only the resources and systems a test asks for.
+
+use std::{fs, ops::Deref, path::PathBuf, sync::Arc};
+
+use amethyst::{
+    assets::{AssetStorage, Loader, ProgressCounter},
+    core::{SystemBundle, Time, Transform},
+    ecs::*,
+    renderer::{Camera, SpriteRender, SpriteSheet, Texture},
+    window::ScreenDimensions,
+};
+use serde::de::DeserializeOwned;
+
+use crate::{
+    assets::{load_spritesheet, SpriteStorage},
+    asteroid::{generate_asteroid, Asteroid, AsteroidType},
+    chemistry::Chemistry,
+    delivery::generate_delivery_zone,
+    economy::Enterprise,
+    explosions::{generate_explosion, Explosion},
+    level::Level,
+    materials::Materials,
+    particles::Particle,
+    physics::PhysicsBundle,
+    player::{initialize_player, Player},
+    replay::PlayerInput,
+    rng::GameRng,
+};
+
+pub const TICK: f32 = 1.0 / 60.0;
+
+fn assets_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("assets")
+}
+
+/// Reads one of the game's RON assets straight from disk.
+pub fn load_asset<T: DeserializeOwned>(path: &str) -> T {
+    let bytes = fs::read(assets_dir().join(path)).unwrap();
+    ron::de::from_bytes(&bytes).unwrap()
+}
+
+pub fn level_from_ron(level: &str) -> Level {
+    ron::de::from_str(level).unwrap()
+}
+
+pub struct TestWorldBuilder {
+    world: World,
+    dispatcher: DispatcherBuilder<'static, 'static>,
+}
+
+impl TestWorldBuilder {
+    /// Physics always runs. Chemistry and materials come from the shipped assets; the level is
+    /// empty and the enterprise is brand new.
+    pub fn new() -> Self {
+        let mut world = World::new();
+        let pool = Arc::new(rayon::ThreadPoolBuilder::new().build().unwrap());
+        world.insert(Loader::new(assets_dir(), pool));
+        world.insert(AssetStorage::<Texture>::default());
+        world.insert(AssetStorage::<SpriteSheet>::default());
+        // The sheet is never processed, but sprites still need a handle to hold.
+        let sprites = load_spritesheet(&mut world, "Sprites", &mut ProgressCounter::new());
+        world.insert(SpriteStorage { sprites });
+        let mut time = Time::default();
+        time.set_delta_seconds(TICK);
+        world.insert(time);
+        world.insert(load_asset::<Chemistry>("reactions.ron"));
+        world.insert(load_asset::<Materials>("materials.ron"));
+        world.insert(Level::default());
+        world.insert(Enterprise::begin_enterprise());
+        world.insert(GameRng::new(Some(0)));
+        world.insert(PlayerInput::default());
+        world.insert(ScreenDimensions::new(1024, 768, 1.0));
+
+        let mut dispatcher = DispatcherBuilder::new();
+        PhysicsBundle::default()
+            .build(&mut world, &mut dispatcher)
+            .unwrap();
+        TestWorldBuilder { world, dispatcher }
+    }
+
+    pub fn with<S>(mut self, system: S, name: &str) -> Self
+    where
+        S: for<'c> System<'c> + Send + 'static,
+    {
+        self.dispatcher.add(system, name, &[]);
+        self
+    }
+
+    pub fn with_level(mut self, level: Level) -> Self {
+        self.world.insert(level);
+        self
+    }
+
+    pub fn with_enterprise(mut self, enterprise: Enterprise) -> Self {
+        self.world.insert(enterprise);
+        self
+    }
+
+    pub fn build(mut self) -> TestWorld {
+        let mut dispatcher = self.dispatcher.build();
+        dispatcher.setup(&mut self.world);
+        // Entities made through `LazyUpdate` need storages the chosen systems may not set up.
+        self.world.register::<SpriteRender>();
+        self.world.register::<Explosion>();
+        self.world.register::<Particle>();
+        self.world.register::<Player>();
+        self.world.register::<Camera>();
+        TestWorld {
+            world: self.world,
+            dispatcher,
+        }
+    }
+}
+
+pub struct TestWorld {
+    pub world: World,
+    dispatcher: Dispatcher<'static, 'static>,
+}
+
+impl TestWorld {
+    fn sprites(&self) -> amethyst::renderer::sprite::SpriteSheetHandle {
+        self.world.read_resource::<SpriteStorage>().sprites.clone()
+    }
+
+    fn at(x: f32, y: f32) -> Transform {
+        let mut transform = Transform::default();
+        transform.set_translation_xyz(x, y, 0.0);
+        transform
+    }
+
+    pub fn spawn_asteroid(&mut self, asteroid_type: AsteroidType, (x, y): (f32, f32)) {
+        let sprites = self.sprites();
+        let materials = { self.world.read_resource::<Materials>().deref().clone() };
+        generate_asteroid(
+            self.world.create_entity(),
+            sprites,
+            &materials,
+            asteroid_type,
+            Self::at(x, y),
+        );
+    }
+
+    pub fn spawn_player(&mut self, (x, y): (f32, f32)) {
+        initialize_player(&mut self.world, Self::at(x, y));
+    }
+
+    pub fn spawn_delivery_zone(&mut self, (x, y): (f32, f32)) {
+        generate_delivery_zone(&mut self.world, (75.0, 75.0), Self::at(x, y));
+    }
+
+    pub fn spawn_explosion(&mut self, (x, y): (f32, f32), strength: f32) {
+        let sprites = self.sprites();
+        generate_explosion(
+            self.world.create_entity(),
+            sprites,
+            Self::at(x, y),
+            (strength, vec![36]),
+        );
+    }
+
+    /// Holds the given input for every following tick.
+    pub fn hold(&mut self, input: PlayerInput) {
+        self.world.insert(PlayerInput {
+            delta_seconds: TICK,
+            ..input
+        });
+    }
+
+    pub fn run(&mut self, ticks: usize) {
+        for _ in 0..ticks {
+            self.dispatcher.dispatch(&self.world);
+            self.world.maintain();
+        }
+    }
+
+    /// The surviving asteroid types, sorted so tests don't depend on entity order.
+    pub fn asteroid_types(&self) -> Vec<AsteroidType> {
+        let asteroids = self.world.read_storage::<Asteroid>();
+        let mut types: Vec<AsteroidType> = (&asteroids)
+            .join()
+            .map(|asteroid| asteroid.my_type.clone())
+            .collect();
+        types.sort_by_key(|asteroid_type| format!("{:?}", asteroid_type));
+        types
+    }
+
+    pub fn assert_asteroids(&self, expected: &[AsteroidType]) {
+        let mut expected = expected.to_vec();
+        expected.sort_by_key(|asteroid_type| format!("{:?}", asteroid_type));
+        assert_eq!(self.asteroid_types(), expected);
+    }
+
+    pub fn funds(&self) -> u64 {
+        self.world.read_resource::<Enterprise>().funds()
+    }
+
+    pub fn fuel(&self) -> f64 {
+        self.world.read_resource::<Enterprise>().fuel()
+    }
+}
@@ -6,22 +6,23 @@ use amethyst::{
         Time, Transform,
     },
     ecs::*,
-    input::{InputHandler, StringBindings},
     prelude::*,
     renderer::{
         palette::Srgb, resources::Tint, sprite::SpriteSheetHandle, ActiveCamera, Camera,
         SpriteRender,
     },
     window::ScreenDimensions,
-    winit::MouseButton,
 };
+use rand::Rng;
 
 use crate::{
     assets::{SpriteHandles, SpriteRes, SpriteStorage},
     asteroid::Asteroid,
     economy::Enterprise,
     particles::{emit_particle, random_direction, Particle},
-    physics::{Physics, PhysicsDesc, PhysicsHandle},
+    physics::{Physics, PhysicsClock, PhysicsDesc, PhysicsHandle},
+    replay::PlayerInput,
+    rng::GameRng,
 };
 
 #[derive(Component, Debug)]
@@ -63,15 +64,13 @@ fn move_tractor<'s>(
 pub struct PlayerTractorSystem;
 impl<'s> System<'s> for PlayerTractorSystem {
     type SystemData = (
-        Read<'s, InputHandler<StringBindings>>,
+        Read<'s, PlayerInput>,
         WriteStorage<'s, Tractor>,
         Option<Read<'s, SpriteStorage>>,
         Read<'s, LazyUpdate>,
-        ReadStorage<'s, Camera>,
-        Option<Read<'s, ScreenDimensions>>,
         WriteStorage<'s, Transform>,
         Write<'s, Enterprise>,
-        Read<'s, Time>,
+        Read<'s, PhysicsClock>,
         Entities<'s>,
     );
 
@@ -82,33 +81,17 @@ impl<'s> System<'s> for PlayerTractorSystem {
             mut tractors,
             sprites,
             update,
-            cameras,
-            dimensions,
             mut transforms,
             mut enterprise,
-            time,
+            clock,
             entities,
         ): Self::SystemData,
     ) {
-        let location = {
-            if let Some((transform, camera)) = (&transforms, &cameras).join().next() {
-                if let Some((x, y)) = input.mouse_position() {
-                    Some(camera.screen_to_world_point(
-                        Point3::new(x, y, 0.0),
-                        dimensions.unwrap().diagonal(),
-                        transform,
-                    ))
-                } else {
-                    None
-                }
-            } else {
-                None
-            }
-        };
+        let location = input.target();
         if let Some((tractor, entity)) = (&mut tractors, &entities).join().next() {
             let entity = entity.clone();
-            if input.mouse_button_is_down(MouseButton::Left) {
-                enterprise.eat_fuel(0.5, &time);
+            if input.tractor {
+                enterprise.eat_fuel(0.5, clock.stepped() as f64);
                 if let Some(location) = location {
                     move_tractor(entity, tractor, &mut transforms, location);
                 }
@@ -116,16 +99,10 @@ impl<'s> System<'s> for PlayerTractorSystem {
                 entities.delete(entity);
             }
 
-            let strength_change = input.axis_value("strength");
-            let attenuation_change = input.axis_value("attenuation");
-            if let (Some(strength_change), Some(attenuation_change)) =
-                (strength_change, attenuation_change)
-            {
-                tractor.strength += strength_change;
-                tractor.attenuation += attenuation_change;
-            }
-        } else if input.mouse_button_is_down(MouseButton::Left) {
-            enterprise.eat_fuel(0.05, &time);
+            tractor.strength += input.strength;
+            tractor.attenuation += input.attenuation;
+        } else if input.tractor {
+            enterprise.eat_fuel(0.05, clock.stepped() as f64);
             if let Some(location) = location {
                 init_tractor(
                     update.create_entity(&entities),
@@ -148,20 +125,31 @@ impl<'s> System<'s> for TractorGravitySystem {
         Entities<'s>,
         Write<'s, Physics>,
         SpriteRes<'s>,
+        Write<'s, GameRng>,
     );
 
     fn run(
         &mut self,
-        (tractors, transforms, handles, asteroids, update, entities, mut physics, sprites): Self::SystemData,
+        (
+            tractors,
+            transforms,
+            handles,
+            asteroids,
+            update,
+            entities,
+            mut physics,
+            sprites,
+            mut rng,
+        ): Self::SystemData,
     ) {
         for (tractor, transform) in (&tractors, &transforms).join() {
-            if rand::random::<f32>() > 0.1 {
+            if rng.gen::<f32>() > 0.1 {
                 let translation = transform.translation();
-                let direction = random_direction();
+                let direction = random_direction(&mut *rng);
                 emit_particle(
                     update.create_entity(&entities),
                     sprites.get_handle(),
-                    Particle::tractor_pull(direction),
+                    Particle::tractor_pull(direction, &mut *rng),
                     nalgebra::Point2::new(
                         translation.x - direction.x * 30.0,
                         translation.y - direction.y * 30.0,
@@ -180,7 +168,7 @@ impl<'s> System<'s> for TractorGravitySystem {
                     if distance > 100.0 {
                         strength = 0.0;
                     } else if distance > 50.0 {
-                        if rand::random::<f32>() > 0.9 {
+                        if rng.gen::<f32>() > 0.9 {
                             emit_particle(
                                 update.create_entity(&entities),
                                 sprites.get_handle(),
@@ -191,11 +179,11 @@ impl<'s> System<'s> for TractorGravitySystem {
                         strength = strength * 5.0;
                         physics.apply_dampening(handle, 1.0);
                     } else if distance > 5.0 {
-                        if rand::random::<f32>() > 0.9 {
+                        if rng.gen::<f32>() > 0.9 {
                             emit_particle(
                                 update.create_entity(&entities),
                                 sprites.get_handle(),
-                                Particle::tractor_light(difference.normalize()),
+                                Particle::tractor_light(difference.normalize(), &mut *rng),
                                 asteroid_location,
                             );
                         }
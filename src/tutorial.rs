@@ -0,0 +1,305 @@
+//! Tutorial scripts: a level can list steps that each wait for something the player does and then
+//! show or spawn whatever teaches the next thing.
+
+use amethyst::{
+    core::{HiddenPropagate, SystemBundle, Time, Transform},
+    ecs::*,
+    prelude::*,
+    shrev::{EventChannel, ReaderId},
+    ui::{UiText, UiTransform},
+    Error,
+};
+
+use crate::{
+    assets::SpriteRes,
+    asteroid::{generate_asteroid, AsteroidType},
+    billboards::{generate_billboard, BillboardDesc},
+    events::{AsteroidDelivered, AsteroidReacted},
+    level::Level,
+    materials::Materials,
+    menu::find_by_id,
+    player::Player,
+    replay::PlayerInput,
+};
+
+/// How long a highlighted element blinks for, in seconds.
+const HIGHLIGHT_SECONDS: f32 = 3.0;
+/// How long each blink lasts.
+const BLINK_SECONDS: f32 = 0.25;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum Trigger {
+    /// Straight away.
+    Start,
+    /// The player flying within `radius` of `location`.
+    Reach { location: (f32, f32), radius: f32 },
+    /// The player switching the tractor on.
+    Tractor,
+    /// Any asteroid being delivered.
+    Delivery,
+    /// Any two asteroids reacting.
+    Reaction,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub enum Action {
+    /// Replaces the tutorial text on the HUD.
+    Text(String),
+    Billboard(BillboardDesc),
+    /// Makes the HUD element with this id blink for a moment.
+    Highlight(String),
+    Spawn {
+        asteroid: AsteroidType,
+        location: (f32, f32),
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TutorialStep {
+    pub trigger: Trigger,
+    pub actions: Vec<Action>,
+}
+
+/// How far through the level's tutorial the player is.
+#[derive(Default)]
+pub struct TutorialProgress {
+    pub step: usize,
+    pub text: String,
+    /// The element being highlighted and how much longer it blinks for.
+    pub highlight: Option<(String, f32)>,
+}
+
+#[derive(Default)]
+pub struct TutorialSystem {
+    delivered: Option<ReaderId<AsteroidDelivered>>,
+    reacted: Option<ReaderId<AsteroidReacted>>,
+}
+
+impl<'s> System<'s> for TutorialSystem {
+    type SystemData = (
+        Read<'s, EventChannel<AsteroidDelivered>>,
+        Read<'s, EventChannel<AsteroidReacted>>,
+        Read<'s, Level>,
+        Write<'s, TutorialProgress>,
+        Read<'s, PlayerInput>,
+        ReadStorage<'s, Player>,
+        ReadStorage<'s, Transform>,
+        Read<'s, LazyUpdate>,
+        Entities<'s>,
+        SpriteRes<'s>,
+        Read<'s, Materials>,
+    );
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.delivered = Some(
+            world
+                .write_resource::<EventChannel<AsteroidDelivered>>()
+                .register_reader(),
+        );
+        self.reacted = Some(
+            world
+                .write_resource::<EventChannel<AsteroidReacted>>()
+                .register_reader(),
+        );
+    }
+
+    fn run(
+        &mut self,
+        (
+            delivered,
+            reacted,
+            level,
+            mut progress,
+            input,
+            players,
+            transforms,
+            update,
+            entities,
+            sprites,
+            materials,
+        ): Self::SystemData,
+    ) {
+        // Events are read every frame so that only what happens once a step is reached counts.
+        let delivery = self
+            .delivered
+            .as_mut()
+            .map_or(false, |reader| delivered.read(reader).count() > 0);
+        let reaction = self
+            .reacted
+            .as_mut()
+            .map_or(false, |reader| reacted.read(reader).count() > 0);
+        let player = (&players, &transforms)
+            .join()
+            .next()
+            .map(|(_, transform)| (transform.translation().x, transform.translation().y));
+
+        while let Some(step) = level.tutorial.get(progress.step) {
+            let triggered = match &step.trigger {
+                Trigger::Start => true,
+                Trigger::Reach { location, radius } => player.map_or(false, |(x, y)| {
+                    (x - location.0).hypot(y - location.1) <= *radius
+                }),
+                Trigger::Tractor => input.tractor,
+                Trigger::Delivery => delivery,
+                Trigger::Reaction => reaction,
+            };
+            if !triggered {
+                break;
+            }
+            for action in step.actions.iter() {
+                match action {
+                    Action::Text(text) => progress.text = text.clone(),
+                    Action::Highlight(id) => {
+                        progress.highlight = Some((id.clone(), HIGHLIGHT_SECONDS))
+                    }
+                    Action::Billboard(desc) => {
+                        if let Some(sprites) = &sprites {
+                            generate_billboard(
+                                update.create_entity(&entities),
+                                sprites.sprites.clone(),
+                                desc,
+                            );
+                        }
+                    }
+                    Action::Spawn { asteroid, location } => {
+                        if let Some(sprites) = &sprites {
+                            let mut transform = Transform::default();
+                            transform.set_translation_xyz(location.0, location.1, 0.0);
+                            generate_asteroid(
+                                update.create_entity(&entities),
+                                sprites.sprites.clone(),
+                                &materials,
+                                asteroid.clone(),
+                                transform,
+                            );
+                        }
+                    }
+                }
+            }
+            progress.step += 1;
+        }
+    }
+}
+
+/// Shows the tutorial text and blinks whatever is highlighted.
+pub struct TutorialHudSystem;
+impl<'s> System<'s> for TutorialHudSystem {
+    type SystemData = (
+        Entities<'s>,
+        WriteStorage<'s, UiTransform>,
+        WriteStorage<'s, UiText>,
+        WriteStorage<'s, HiddenPropagate>,
+        Write<'s, TutorialProgress>,
+        Read<'s, Time>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, transforms, mut texts, mut hiddens, mut progress, time): Self::SystemData,
+    ) {
+        if let Some(label) = find_by_id(&entities, &transforms, "tutorial") {
+            if let Some(text) = texts.get_mut(label) {
+                if text.text != progress.text {
+                    text.text = progress.text.clone();
+                }
+            }
+        }
+        if let Some((id, remaining)) = &mut progress.highlight {
+            *remaining -= time.delta_seconds();
+            let blinking = *remaining > 0.0;
+            if let Some(element) = find_by_id(&entities, &transforms, id) {
+                if blinking && (*remaining / BLINK_SECONDS) as u32 % 2 == 0 {
+                    hiddens.insert(element, HiddenPropagate::new()).ok();
+                } else {
+                    hiddens.remove(element);
+                }
+            }
+            if !blinking {
+                progress.highlight = None;
+            }
+        }
+    }
+}
+
+pub struct TutorialBundle;
+impl<'a, 'b> SystemBundle<'a, 'b> for TutorialBundle {
+    fn build(
+        self,
+        _world: &mut World,
+        dispatcher: &mut DispatcherBuilder<'a, 'b>,
+    ) -> Result<(), Error> {
+        dispatcher.add(TutorialSystem::default(), "tutorial", &[]);
+        dispatcher.add(TutorialHudSystem, "tutorial_hud", &[]);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        asteroid::Asteroid,
+        billboards::Billboard,
+        physics::{Physics, PhysicsHandle},
+        test_support::{level_from_ron, TestWorldBuilder},
+    };
+
+    #[test]
+    fn steps_wait_for_their_triggers() {
+        let level = level_from_ron(
+            r#"#![enable(implicit_some)]
+            Level(
+                boundaries: (500.0, 500.0),
+                deliveries: [(0.0, 400.0)],
+                jump_cost: 100,
+                asteroids: [],
+                billboards: [],
+                card: (title: "Test", sprite_number: 0),
+                reference: (name: "Test", description: "", shown_prices: []),
+                tutorial: [(
+                    trigger: Start,
+                    actions: [Text("Fly up"), Billboard((location: (0.0, 50.0), sprite_number: 45))],
+                ), (
+                    trigger: Reach(location: (0.0, 50.0), radius: 20.0),
+                    actions: [Text("Pull"), Spawn(asteroid: Big, location: (0.0, 100.0))],
+                ), (
+                    trigger: Tractor,
+                    actions: [Text("Done")],
+                )],
+            )"#,
+        );
+        let mut test = TestWorldBuilder::new()
+            .with(TutorialSystem::default(), "tutorial")
+            .with_level(level)
+            .build();
+        test.world.register::<Billboard>();
+        test.world.register::<Asteroid>();
+        test.spawn_player((0.0, 0.0));
+        test.run(2);
+        assert_eq!(test.world.read_resource::<TutorialProgress>().step, 1);
+        assert_eq!(test.world.read_storage::<Billboard>().join().count(), 1);
+
+        {
+            let handle = {
+                let players = test.world.read_storage::<Player>();
+                let handles = test.world.read_storage::<PhysicsHandle>();
+                (&players, &handles).join().next().unwrap().1.clone()
+            };
+            test.world
+                .write_resource::<Physics>()
+                .set_location(&handle, 0.0, 40.0);
+        }
+        test.run(2);
+        assert_eq!(test.world.read_resource::<TutorialProgress>().text, "Pull");
+        test.assert_asteroids(&[AsteroidType::Big]);
+
+        test.hold(PlayerInput {
+            tractor: true,
+            ..PlayerInput::default()
+        });
+        test.run(1);
+        let progress = test.world.read_resource::<TutorialProgress>();
+        assert_eq!((progress.step, progress.text.as_str()), (3, "Done"));
+    }
+}